@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Consecutive connection failures after which a host is considered down
+const FAILURE_THRESHOLD: u32 = 3;
+const COOL_DOWN_SECS: u64 = 600;
+
+#[derive(Default)]
+struct HostState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Per-host circuit breaker for hosts that fail to resolve or connect
+/// Once a cool-down ends the next request is let through; a single further failure reopens the circuit
+pub struct HostCircuitBreaker {
+    hosts: Mutex<HashMap<String, HostState>>,
+}
+
+impl HostCircuitBreaker {
+    pub fn new() -> Self {
+        Self {
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Time left before requests to the host are allowed again, or None if the circuit is closed
+    pub fn open_for(&self, host: &str) -> Option<Duration> {
+        let hosts = self.hosts.lock().unwrap();
+        let open_until = hosts.get(host)?.open_until?;
+        open_until.checked_duration_since(Instant::now())
+    }
+
+    pub fn record_success(&self, host: &str) {
+        self.hosts.lock().unwrap().remove(host);
+    }
+
+    /// Count a failed connection, opening the circuit once the threshold is reached
+    pub fn record_failure(&self, host: &str) {
+        let mut hosts = self.hosts.lock().unwrap();
+        let state = hosts.entry(host.to_string()).or_default();
+        state.consecutive_failures += 1;
+        if state.consecutive_failures >= FAILURE_THRESHOLD {
+            state.open_until = Some(Instant::now() + Duration::from_secs(COOL_DOWN_SECS));
+        }
+    }
+}
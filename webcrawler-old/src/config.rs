@@ -0,0 +1,131 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Runtime settings read from a JSON file; missing fields use their defaults
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CrawlerConfig {
+    /// Probe suspect URLs (binary-looking extensions, download endpoints) with HEAD before fetching them
+    pub head_probing: bool,
+    /// Proxy URLs (`http://`, `https://`, `socks5://`, `socks5h://`); empty means direct connections
+    pub proxies: Vec<String>,
+    /// URL fetched through each proxy by the periodic health check
+    pub proxy_check_url: String,
+    /// CIDR ranges, IPs or host names exempt from the private address block, for intranet crawls
+    pub private_allowlist: Vec<String>,
+    /// Request settings applied to matching hosts
+    pub profiles: Vec<RequestProfile>,
+    /// Leave pages marked noindex out of the output
+    pub respect_noindex: bool,
+    /// Keep links of nofollow pages and rel="nofollow"/"ugc"/"sponsored" links out of the frontier
+    pub respect_nofollow: bool,
+    /// Unicode normalization applied to extracted text
+    pub unicode_normalization: UnicodeForm,
+    /// Download page images to save thumbnails; otherwise image bytes are never fetched
+    pub image_thumbnails: bool,
+    /// Languages whose hreflang editions of a page are crawled first, e.g. `["en", "de"]`
+    pub preferred_languages: Vec<String>,
+    /// Languages whose hreflang editions of a page are not crawled
+    pub excluded_languages: Vec<String>,
+    /// Start a new output segment once the current one holds this many bytes of uncompressed JSONL
+    pub segment_max_bytes: u64,
+    /// Start a new output segment once the current one has been open this many seconds
+    pub segment_max_secs: u64,
+    /// Compression of output segments
+    pub output_compression: OutputCompression,
+}
+
+impl Default for CrawlerConfig {
+    fn default() -> Self {
+        Self {
+            head_probing: true,
+            proxies: Vec::new(),
+            proxy_check_url: "https://www.wikipedia.org/".to_string(),
+            private_allowlist: Vec::new(),
+            profiles: Vec::new(),
+            respect_noindex: true,
+            respect_nofollow: true,
+            unicode_normalization: UnicodeForm::Nfc,
+            image_thumbnails: false,
+            preferred_languages: Vec::new(),
+            excluded_languages: Vec::new(),
+            segment_max_bytes: 1024 * 1024 * 1024,
+            segment_max_secs: 3600,
+            output_compression: OutputCompression::None,
+        }
+    }
+}
+
+/// Headers, credentials and cookies for one host and its subdomains
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct RequestProfile {
+    pub host: String,
+    pub headers: HashMap<String, String>,
+    pub auth: Option<ProfileAuth>,
+    pub user_agent: Option<String>,
+    pub accept_language: Option<String>,
+    /// Keep cookies set by the host and send them back
+    pub cookie_jar: bool,
+    /// Save the cookie jar in the URL store so it survives restarts
+    pub persist_cookies: bool,
+    /// Cookies sent from the first request on, e.g. `consent=yes`
+    pub cookies: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnicodeForm {
+    None,
+    /// Canonical composition, e.g. `e` + combining acute becomes `é`
+    Nfc,
+    /// Compatibility composition, which also folds ligatures, full-width forms and the like
+    Nfkc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputCompression {
+    None,
+    /// `.gz` segments, readable by any gzip tool
+    Gzip,
+    /// `.zst` segments, smaller and faster to write and read
+    Zstd,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ProfileAuth {
+    Basic { username: String, password: Option<String> },
+    Bearer { token: String },
+}
+
+impl CrawlerConfig {
+    /// Load the config file, falling back to defaults if it doesn't exist
+    pub fn load(path: &str) -> Result<Self, ConfigError> {
+        if !Path::new(path).exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = std::fs::read_to_string(path).map_err(ConfigError::Io)?;
+        serde_json::from_str(&contents).map_err(ConfigError::Parse)
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "Failed to read config: {}", e),
+            ConfigError::Parse(e) => write!(f, "Invalid config: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
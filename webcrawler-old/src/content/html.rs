@@ -0,0 +1,75 @@
+use super::{decode_body, ContentError, ContentHandler};
+use crate::extractors::Extractors;
+use crate::http_client::FetchedContent;
+use crate::parser::{self, ParsedHtml};
+use encoding_rs::Encoding;
+use std::sync::Arc;
+
+const MAX_HTML_SIZE: usize = 10 * 1024 * 1024;
+
+/// HTML pages, parsed with lol_html and the site-specific extractors
+pub struct HtmlHandler {
+    extractors: Arc<Extractors>,
+}
+
+impl HtmlHandler {
+    pub fn new(extractors: Arc<Extractors>) -> Self {
+        Self { extractors }
+    }
+}
+
+impl ContentHandler for HtmlHandler {
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["text/html"]
+    }
+
+    fn max_size(&self) -> usize {
+        MAX_HTML_SIZE
+    }
+
+    fn extract(&self, content: &FetchedContent, url: &str) -> Result<ParsedHtml, ContentError> {
+        parser::parse_html(decode_body(content, None), url, &self.extractors).map_err(ContentError::Parse)
+    }
+}
+
+/// XHTML pages: parsed like HTML, but the charset may come from the XML declaration
+pub struct XhtmlHandler {
+    extractors: Arc<Extractors>,
+}
+
+impl XhtmlHandler {
+    pub fn new(extractors: Arc<Extractors>) -> Self {
+        Self { extractors }
+    }
+}
+
+impl ContentHandler for XhtmlHandler {
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/xhtml+xml"]
+    }
+
+    fn max_size(&self) -> usize {
+        MAX_HTML_SIZE
+    }
+
+    fn extract(&self, content: &FetchedContent, url: &str) -> Result<ParsedHtml, ContentError> {
+        let declared = xml_declared_encoding(&content.body);
+        let mut parsed = parser::parse_html(decode_body(content, declared), url, &self.extractors).map_err(ContentError::Parse)?;
+        parsed.content_type = content.mime_type.clone();
+        Ok(parsed)
+    }
+}
+
+/// Encoding named in an `<?xml version="1.0" encoding="..."?>` declaration
+fn xml_declared_encoding(body: &[u8]) -> Option<&'static Encoding> {
+    let prolog = body.strip_prefix(b"\xef\xbb\xbf").unwrap_or(body);
+    let prolog = prolog.strip_prefix(b"<?xml")?;
+    let end = prolog.windows(2).position(|w| w == b"?>")?;
+    let declaration = std::str::from_utf8(&prolog[..end]).ok()?;
+
+    let (_, rest) = declaration.split_once("encoding")?;
+    let value = rest.trim_start().strip_prefix('=')?.trim_start();
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let label = value[1..].split(quote).next()?;
+    Encoding::for_label(label.as_bytes())
+}
@@ -0,0 +1,135 @@
+mod html;
+mod ooxml;
+mod pdf;
+mod text;
+
+use crate::extractors::Extractors;
+use crate::http_client::FetchedContent;
+use crate::language;
+use crate::normalize::collapse_whitespace;
+use crate::parser::{ParseError, ParsedHtml};
+use encoding_rs::{Encoding, UTF_8};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use html::{HtmlHandler, XhtmlHandler};
+pub use ooxml::OoxmlHandler;
+pub use pdf::PdfHandler;
+pub use text::{MarkdownHandler, TextHandler};
+
+const DEFAULT_EXTRACT_TIMEOUT_SECS: u64 = 30;
+
+/// Turns a fetched body of one family of content types into a crawl record
+pub trait ContentHandler: Send + Sync {
+    /// MIME types handled, as lowercase essences without parameters
+    fn mime_types(&self) -> &'static [&'static str];
+
+    /// Largest body this handler will accept
+    fn max_size(&self) -> usize;
+
+    /// Longest a single extraction may run before it is abandoned
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(DEFAULT_EXTRACT_TIMEOUT_SECS)
+    }
+
+    fn extract(&self, content: &FetchedContent, url: &str) -> Result<ParsedHtml, ContentError>;
+}
+
+/// Registry of content handlers keyed by MIME type
+pub struct ContentHandlers {
+    handlers: HashMap<&'static str, Arc<dyn ContentHandler>>,
+}
+
+impl ContentHandlers {
+    pub fn new() -> Self {
+        Self {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registry with the built-in HTML, XHTML, text, Markdown, PDF and OOXML handlers
+    /// HTML and XHTML pages go through the built-in site-specific extractors
+    pub fn with_defaults() -> Self {
+        let extractors = Arc::new(Extractors::with_defaults());
+        let mut handlers = Self::new();
+        handlers.register(Arc::new(HtmlHandler::new(extractors.clone())));
+        handlers.register(Arc::new(XhtmlHandler::new(extractors)));
+        handlers.register(Arc::new(TextHandler));
+        handlers.register(Arc::new(MarkdownHandler));
+        handlers.register(Arc::new(PdfHandler));
+        handlers.register(Arc::new(OoxmlHandler));
+        handlers
+    }
+
+    /// Register a handler for all of its MIME types, replacing earlier registrations
+    pub fn register(&mut self, handler: Arc<dyn ContentHandler>) {
+        for mime_type in handler.mime_types() {
+            self.handlers.insert(mime_type, handler.clone());
+        }
+    }
+
+    /// Size limit for a MIME type, or None if no handler accepts it
+    pub fn max_size(&self, mime_type: &str) -> Option<usize> {
+        self.handlers.get(mime_type).map(|handler| handler.max_size())
+    }
+
+    /// Extract a record on the blocking pool, bounded by the handler's timeout
+    /// A timed-out extraction keeps its blocking thread until it finishes, but the caller moves on
+    pub async fn extract(&self, content: FetchedContent, url: &str) -> Result<ParsedHtml, ContentError> {
+        let handler = self
+            .handlers
+            .get(content.mime_type.as_str())
+            .cloned()
+            .ok_or_else(|| ContentError::Unsupported(content.mime_type.clone()))?;
+
+        let timeout = handler.timeout();
+        let url = url.to_string();
+        let task = tokio::task::spawn_blocking(move || {
+            let mut parsed = handler.extract(&content, &url)?;
+            language::identify(&mut parsed);
+            Ok(parsed)
+        });
+
+        match tokio::time::timeout(timeout, task).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(ContentError::Panicked),
+            Err(_) => Err(ContentError::Timeout(timeout)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ContentError {
+    Unsupported(String),
+    Malformed(String),
+    Parse(ParseError),
+    Timeout(Duration),
+    Panicked,
+}
+
+impl std::fmt::Display for ContentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContentError::Unsupported(mime) => write!(f, "No content handler for: {}", mime),
+            ContentError::Malformed(e) => write!(f, "Malformed document: {}", e),
+            ContentError::Parse(e) => write!(f, "{}", e),
+            ContentError::Timeout(limit) => write!(f, "Extraction timed out after {}s", limit.as_secs()),
+            ContentError::Panicked => write!(f, "Extraction panicked"),
+        }
+    }
+}
+
+impl std::error::Error for ContentError {}
+
+/// Decode a textual body using its charset, a byte order mark, or UTF-8
+fn decode_body(content: &FetchedContent, fallback: Option<&'static Encoding>) -> String {
+    let encoding = content
+        .charset
+        .as_deref()
+        .and_then(|charset| Encoding::for_label(charset.as_bytes()))
+        .or(fallback)
+        .unwrap_or(UTF_8);
+    let (text, _, _) = encoding.decode(&content.body);
+    text.into_owned()
+}
@@ -0,0 +1,146 @@
+use super::{collapse_whitespace, ContentError, ContentHandler};
+use crate::http_client::FetchedContent;
+use crate::parser::{self, Link, ParsedHtml};
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+const MAX_OOXML_SIZE: usize = 25 * 1024 * 1024;
+/// Total bytes decompressed from one archive, guards against zip bombs
+const MAX_UNCOMPRESSED_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Archive parts holding document text, matched by prefix
+const TEXT_PARTS: [&str; 5] = [
+    "word/document.xml",
+    "word/footnotes.xml",
+    "ppt/slides/slide",
+    "ppt/notesSlides/notesSlide",
+    "xl/sharedStrings.xml",
+];
+
+/// Word, PowerPoint and Excel documents in Office Open XML format
+pub struct OoxmlHandler;
+
+impl ContentHandler for OoxmlHandler {
+    fn mime_types(&self) -> &'static [&'static str] {
+        &[
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        ]
+    }
+
+    fn max_size(&self) -> usize {
+        MAX_OOXML_SIZE
+    }
+
+    fn extract(&self, content: &FetchedContent, url: &str) -> Result<ParsedHtml, ContentError> {
+        let mut archive = ZipArchive::new(Cursor::new(content.body.as_slice()))
+            .map_err(|e| ContentError::Malformed(e.to_string()))?;
+        let mut budget = MAX_UNCOMPRESSED_SIZE;
+
+        // Sort slide parts numerically so slide10 follows slide9
+        let mut part_names: Vec<String> = archive
+            .file_names()
+            .filter(|name| name.ends_with(".xml") && TEXT_PARTS.iter().any(|prefix| name.starts_with(prefix)))
+            .map(str::to_string)
+            .collect();
+        part_names.sort_by(|a, b| (a.len(), a).cmp(&(b.len(), b)));
+
+        let mut paragraphs = Vec::new();
+        for name in &part_names {
+            let xml = read_part(&mut archive, name, &mut budget)?;
+            if let Ok(doc) = roxmltree::Document::parse(&xml) {
+                paragraphs.extend(part_paragraphs(&doc));
+            }
+        }
+
+        let mut title = None;
+        let mut language = None;
+        if let Ok(xml) = read_part(&mut archive, "docProps/core.xml", &mut budget)
+            && let Ok(doc) = roxmltree::Document::parse(&xml) {
+            title = element_text(&doc, "title");
+            language = element_text(&doc, "language");
+        }
+
+        // Hyperlinks are external relationships in the parts' .rels files
+        let rels_names: Vec<String> = archive
+            .file_names()
+            .filter(|name| name.ends_with(".rels"))
+            .map(str::to_string)
+            .collect();
+        let mut links = Vec::new();
+        for name in &rels_names {
+            let xml = read_part(&mut archive, name, &mut budget)?;
+            let Ok(doc) = roxmltree::Document::parse(&xml) else {
+                continue;
+            };
+            links.extend(
+                doc.descendants()
+                    .filter(|node| node.tag_name().name() == "Relationship")
+                    .filter(|node| node.attribute("TargetMode") == Some("External"))
+                    .filter(|node| node.attribute("Type").is_some_and(|t| t.ends_with("/hyperlink")))
+                    .filter_map(|node| node.attribute("Target"))
+                    .filter(|target| parser::is_crawlable(target))
+                    .map(|target| Link::new(target.to_string(), url)),
+            );
+        }
+
+        let full_text = collapse_whitespace(&paragraphs.join(" "));
+
+        Ok(ParsedHtml {
+            url: url.to_string(),
+            content_type: content.mime_type.clone(),
+            language,
+            title,
+            main_text: full_text.clone(),
+            full_text,
+            links,
+            ..Default::default()
+        })
+    }
+}
+
+/// Read one archive part, charging its size against the decompression budget
+fn read_part(archive: &mut ZipArchive<Cursor<&[u8]>>, name: &str, budget: &mut u64) -> Result<String, ContentError> {
+    let file = archive
+        .by_name(name)
+        .map_err(|e| ContentError::Malformed(e.to_string()))?;
+
+    let mut xml = String::new();
+    file.take(*budget + 1)
+        .read_to_string(&mut xml)
+        .map_err(|e| ContentError::Malformed(e.to_string()))?;
+    if xml.len() as u64 > *budget {
+        return Err(ContentError::Malformed("decompressed document too large".to_string()));
+    }
+    *budget -= xml.len() as u64;
+
+    Ok(xml)
+}
+
+/// Text of each top-level paragraph (`w:p`, `a:p`) or shared string (`si`)
+/// Runs inside a paragraph are concatenated without spaces, since Word splits runs mid-word
+fn part_paragraphs(doc: &roxmltree::Document) -> Vec<String> {
+    let is_paragraph = |node: &roxmltree::Node| matches!(node.tag_name().name(), "p" | "si");
+
+    doc.descendants()
+        .filter(|node| node.is_element() && is_paragraph(node))
+        .filter(|node| !node.ancestors().skip(1).any(|ancestor| is_paragraph(&ancestor)))
+        .map(|paragraph| {
+            paragraph
+                .descendants()
+                .filter(|node| node.tag_name().name() == "t")
+                .filter_map(|node| node.text())
+                .collect::<String>()
+        })
+        .filter(|text| !text.trim().is_empty())
+        .collect()
+}
+
+fn element_text(doc: &roxmltree::Document, name: &str) -> Option<String> {
+    doc.descendants()
+        .find(|node| node.tag_name().name() == name)
+        .and_then(|node| node.text())
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
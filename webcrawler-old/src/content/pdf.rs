@@ -0,0 +1,98 @@
+use super::{collapse_whitespace, ContentError, ContentHandler};
+use crate::http_client::FetchedContent;
+use crate::parser::{self, Link, ParsedHtml};
+use pdf_extract::{Document, Object, PlainTextOutput};
+use std::time::Duration;
+
+const MAX_PDF_SIZE: usize = 25 * 1024 * 1024;
+const PDF_EXTRACT_TIMEOUT_SECS: u64 = 60;
+
+/// PDF documents: text, Info title, catalog language and URI link annotations
+pub struct PdfHandler;
+
+impl ContentHandler for PdfHandler {
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["application/pdf"]
+    }
+
+    fn max_size(&self) -> usize {
+        MAX_PDF_SIZE
+    }
+
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(PDF_EXTRACT_TIMEOUT_SECS)
+    }
+
+    fn extract(&self, content: &FetchedContent, url: &str) -> Result<ParsedHtml, ContentError> {
+        let malformed = |e: &dyn std::fmt::Display| ContentError::Malformed(e.to_string());
+
+        let mut doc = Document::load_mem(&content.body).map_err(|e| malformed(&e))?;
+        if doc.is_encrypted() {
+            // Only documents with an empty user password can be read
+            doc.decrypt("").map_err(|e| malformed(&e))?;
+        }
+
+        let mut text = String::new();
+        pdf_extract::output_doc(&doc, &mut PlainTextOutput::new(&mut text)).map_err(|e| malformed(&e))?;
+
+        let title = doc
+            .trailer
+            .get(b"Info")
+            .ok()
+            .and_then(|info| doc.dereference(info).ok())
+            .and_then(|(_, info)| info.as_dict().ok())
+            .and_then(|info| info.get(b"Title").ok())
+            .and_then(|title| text_string(&doc, title))
+            .filter(|title| !title.is_empty());
+
+        let language = doc
+            .catalog()
+            .ok()
+            .and_then(|catalog| catalog.get(b"Lang").ok())
+            .and_then(|lang| text_string(&doc, lang))
+            .filter(|lang| !lang.is_empty());
+
+        // URI actions (`<< /S /URI /URI (...) >>`) carry the document's hyperlinks
+        let links = doc
+            .objects
+            .values()
+            .filter_map(|object| object.as_dict().ok())
+            .filter_map(|dict| dict.get(b"URI").ok())
+            .filter_map(|uri| text_string(&doc, uri))
+            .filter(|uri| parser::is_crawlable(uri))
+            .map(|uri| Link::new(uri, url))
+            .collect();
+
+        let full_text = collapse_whitespace(&text);
+
+        Ok(ParsedHtml {
+            url: url.to_string(),
+            content_type: content.mime_type.clone(),
+            language,
+            title,
+            main_text: full_text.clone(),
+            full_text,
+            links,
+            ..Default::default()
+        })
+    }
+}
+
+/// Decode a PDF text string: UTF-16BE with a byte order mark, otherwise PDFDocEncoding
+fn text_string(doc: &Document, object: &Object) -> Option<String> {
+    let (_, object) = doc.dereference(object).ok()?;
+    let bytes = object.as_str().ok()?;
+
+    let text = if let Some(utf16) = bytes.strip_prefix(&[0xfe, 0xff]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        // PDFDocEncoding matches Latin-1 for printable text
+        bytes.iter().map(|&b| b as char).collect()
+    };
+
+    Some(text.trim().to_string())
+}
@@ -0,0 +1,151 @@
+use super::{collapse_whitespace, decode_body, ContentError, ContentHandler};
+use crate::http_client::FetchedContent;
+use crate::parser::{self, Link, ParsedHtml};
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use url::Url;
+
+const MAX_TEXT_SIZE: usize = 10 * 1024 * 1024;
+const MAX_TITLE_LEN: usize = 200;
+
+/// Plain text documents: the first line becomes the title, bare URLs become links
+pub struct TextHandler;
+
+impl ContentHandler for TextHandler {
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["text/plain"]
+    }
+
+    fn max_size(&self) -> usize {
+        MAX_TEXT_SIZE
+    }
+
+    fn extract(&self, content: &FetchedContent, url: &str) -> Result<ParsedHtml, ContentError> {
+        let text = decode_body(content, None);
+
+        let title = text
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(|line| line.chars().take(MAX_TITLE_LEN).collect());
+
+        let full_text = collapse_whitespace(&text);
+
+        Ok(ParsedHtml {
+            url: url.to_string(),
+            content_type: content.mime_type.clone(),
+            title,
+            main_text: full_text.clone(),
+            full_text,
+            links: bare_urls(&text).into_iter().map(|link| Link::new(link, url)).collect(),
+            ..Default::default()
+        })
+    }
+}
+
+/// Markdown documents: syntax is stripped, the first heading becomes the title
+pub struct MarkdownHandler;
+
+impl ContentHandler for MarkdownHandler {
+    fn mime_types(&self) -> &'static [&'static str] {
+        &["text/markdown", "text/x-markdown"]
+    }
+
+    fn max_size(&self) -> usize {
+        MAX_TEXT_SIZE
+    }
+
+    fn extract(&self, content: &FetchedContent, url: &str) -> Result<ParsedHtml, ContentError> {
+        let markdown = decode_body(content, None);
+        let base_url = Url::parse(url).map_err(|e| ContentError::Malformed(e.to_string()))?;
+
+        let mut content_text = String::new();
+        let mut links: Vec<Link> = Vec::new();
+        // Index of the link whose text is being collected, None inside a link that isn't crawlable
+        let mut in_link = None;
+        let mut first_h1 = None;
+        let mut first_heading = None;
+        let mut heading: Option<(HeadingLevel, String)> = None;
+
+        let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+        let mut in_metadata = false;
+        for event in Parser::new_ext(&markdown, options) {
+            match event {
+                Event::Start(Tag::MetadataBlock(_)) => in_metadata = true,
+                Event::End(TagEnd::MetadataBlock(_)) => in_metadata = false,
+                Event::Start(Tag::Heading { level, .. }) => heading = Some((level, String::new())),
+                Event::End(TagEnd::Heading(_)) => {
+                    if let Some((level, text)) = heading.take() {
+                        let text = collapse_whitespace(&text);
+                        if level == HeadingLevel::H1 && first_h1.is_none() {
+                            first_h1 = Some(text.clone());
+                        }
+                        first_heading.get_or_insert(text);
+                    }
+                    content_text.push(' ');
+                }
+                Event::Start(Tag::Link { dest_url, title, .. }) => {
+                    in_link = None;
+                    if let Ok(target) = base_url.join(&dest_url)
+                        && parser::is_crawlable(target.as_str()) {
+                        let mut link = Link::new(target.to_string(), url);
+                        link.title = Some(title.to_string()).filter(|title| !title.is_empty());
+                        in_link = Some(links.len());
+                        links.push(link);
+                    }
+                }
+                Event::End(TagEnd::Link) => in_link = None,
+                Event::Text(text) | Event::Code(text) if !in_metadata => {
+                    if let Some((_, heading_text)) = heading.as_mut() {
+                        heading_text.push_str(&text);
+                    }
+                    if let Some(index) = in_link {
+                        links[index].anchor_text.push_str(&text);
+                    }
+                    content_text.push_str(&text);
+                }
+                Event::SoftBreak
+                | Event::HardBreak
+                | Event::End(TagEnd::Paragraph | TagEnd::Item | TagEnd::CodeBlock | TagEnd::TableCell | TagEnd::BlockQuote(_)) => {
+                    content_text.push(' ');
+                }
+                _ => {}
+            }
+        }
+
+        let full_text = collapse_whitespace(&content_text);
+        for link in &mut links {
+            link.anchor_text = collapse_whitespace(&link.anchor_text);
+        }
+
+        Ok(ParsedHtml {
+            url: url.to_string(),
+            content_type: content.mime_type.clone(),
+            title: first_h1.or(first_heading).filter(|title| !title.is_empty()),
+            main_text: full_text.clone(),
+            full_text,
+            links,
+            ..Default::default()
+        })
+    }
+}
+
+/// Absolute http(s) URLs appearing in running text
+fn bare_urls(text: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = ["http://", "https://"].iter().filter_map(|scheme| rest.find(scheme)).min() {
+        let candidate = &rest[start..];
+        let end = candidate
+            .find(|c: char| c.is_whitespace() || "<>\"'()[]{}".contains(c))
+            .unwrap_or(candidate.len());
+        let url = candidate[..end].trim_end_matches(['.', ',', ';', ':', '!', '?']);
+
+        if parser::is_crawlable(url) {
+            urls.push(url.to_string());
+        }
+        rest = &candidate[end..];
+    }
+
+    urls
+}
@@ -0,0 +1,83 @@
+use xxhash_rust::xxh3::{xxh3_128, xxh3_64};
+
+/// Words per shingle hashed into the SimHash fingerprint
+const SHINGLE_WORDS: usize = 4;
+/// Fingerprints this many bits apart or fewer belong to near-duplicate pages
+pub const MAX_SIMHASH_DISTANCE: u32 = 3;
+/// Fingerprints are indexed by each of their 16-bit bands; any two within `MAX_SIMHASH_DISTANCE`
+/// bits have at least one band in common, as there are more bands than differing bits
+pub const SIMHASH_BANDS: usize = 4;
+/// Pages with fewer main text words than this are too thin to call duplicates,
+/// e.g. error and login pages that share a short message
+const MIN_DEDUP_WORDS: usize = 50;
+
+/// Fingerprint of a page's main text, shared by pages that differ only in case, punctuation and spacing
+/// None when the page has too few words to compare
+pub fn content_hash(main_text: &str) -> Option<String> {
+    let words = words(main_text);
+    if words.len() < MIN_DEDUP_WORDS {
+        return None;
+    }
+    Some(format!("{:032x}", xxh3_128(words.join(" ").as_bytes())))
+}
+
+/// SimHash fingerprint of a page's main text over its word shingles
+/// Pages that differ in a few words get fingerprints that differ in a few bits
+/// None when the page has too few words to compare
+pub fn simhash(main_text: &str) -> Option<u64> {
+    let words = words(main_text);
+    if words.len() < MIN_DEDUP_WORDS {
+        return None;
+    }
+
+    let mut weights = [0i64; 64];
+    for shingle in words.windows(SHINGLE_WORDS) {
+        let hash = xxh3_64(shingle.join(" ").as_bytes());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash >> bit & 1 == 1 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+    let fingerprint = weights
+        .iter()
+        .enumerate()
+        .filter(|(_, weight)| **weight > 0)
+        .fold(0, |fingerprint, (bit, _)| fingerprint | 1 << bit);
+    Some(fingerprint)
+}
+
+/// The 16-bit bands of a fingerprint, highest first
+pub fn simhash_bands(fingerprint: u64) -> [u16; SIMHASH_BANDS] {
+    std::array::from_fn(|band| (fingerprint >> (48 - 16 * band)) as u16)
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Lowercased words, with punctuation and spacing dropped
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thin_pages_get_no_fingerprint() {
+        let login = "Sign in to continue. JavaScript is required to use this site.";
+        assert_eq!(content_hash(login), None);
+        assert_eq!(simhash(login), None);
+
+        let article = (0..MIN_DEDUP_WORDS).map(|index| format!("word{}", index)).collect::<Vec<_>>().join(" ");
+        assert!(content_hash(&article).is_some());
+        assert!(simhash(&article).is_some());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::ssrf::AddressGuard;
+use hickory_resolver::TokioResolver;
+use hickory_resolver::config::ResolverConfig;
+use hickory_resolver::name_server::TokioConnectionProvider;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Answers are kept for their record TTL, up to this long
+const POSITIVE_TTL_SECS: u64 = 300;
+/// Hosts that don't exist are remembered for less time than ones that do
+const NEGATIVE_TTL_SECS: u64 = 60;
+const MAX_ENTRIES: usize = 100_000;
+
+enum CachedAnswer {
+    Found(Vec<SocketAddr>),
+    NotFound,
+}
+
+struct CacheEntry {
+    answer: CachedAnswer,
+    expires: Instant,
+}
+
+/// Outcome of resolving a host
+#[derive(Debug, Clone)]
+pub struct DnsLookup {
+    pub addrs: Vec<SocketAddr>,
+    /// Time spent resolving, zero when answered from the cache
+    pub elapsed: Duration,
+}
+
+/// Async resolver using the system's name servers and hosts file, caching answers and NXDOMAIN
+/// Also used by reqwest for its connections, so a host resolved before a request is not looked up twice
+/// Every answer passes through the address guard, so redirects and rebinding can't reach blocked ranges
+pub struct CachingResolver {
+    resolver: TokioResolver,
+    cache: Arc<Mutex<HashMap<String, CacheEntry>>>,
+    guard: Arc<AddressGuard>,
+}
+
+impl CachingResolver {
+    /// Resolver configured from the system, or using public name servers when that can't be read
+    pub fn new(guard: Arc<AddressGuard>) -> Self {
+        let resolver = match TokioResolver::builder_tokio() {
+            Ok(builder) => builder.build(),
+            Err(e) => {
+                eprintln!("Failed to read the system DNS configuration, using defaults: {}", e);
+                TokioResolver::builder_with_config(ResolverConfig::default(), TokioConnectionProvider::default()).build()
+            }
+        };
+        Self {
+            resolver,
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            guard,
+        }
+    }
+
+    /// Resolve a host name, from the cache when possible, dropping addresses the guard refuses
+    pub async fn lookup(&self, host: &str) -> Result<DnsLookup, DnsError> {
+        let host = host.to_ascii_lowercase();
+        let mut lookup = self.lookup_unguarded(&host).await?;
+
+        lookup.addrs.retain(|addr| self.guard.permits(&host, addr.ip()));
+        if lookup.addrs.is_empty() {
+            return Err(DnsError::Blocked(host));
+        }
+        Ok(lookup)
+    }
+
+    async fn lookup_unguarded(&self, host: &str) -> Result<DnsLookup, DnsError> {
+        let host = host.to_string();
+        if let Some(cached) = self.cached(&host) {
+            return cached;
+        }
+
+        let started = Instant::now();
+        let (answer, valid_until) = match self.resolver.lookup_ip(host.as_str()).await {
+            Ok(lookup) => {
+                let addrs: Vec<SocketAddr> = lookup.iter().map(|ip| SocketAddr::new(ip, 0)).collect();
+                if addrs.is_empty() {
+                    (CachedAnswer::NotFound, None)
+                } else {
+                    (CachedAnswer::Found(addrs), Some(lookup.valid_until()))
+                }
+            }
+            // NXDOMAIN, or a name that exists without addresses
+            Err(e) if e.is_nx_domain() || e.is_no_records_found() => (CachedAnswer::NotFound, None),
+            // Timeouts and server failures may be transient, so they are not cached
+            Err(e) => return Err(DnsError::Failed(host.clone(), e.to_string())),
+        };
+        let elapsed = started.elapsed();
+
+        let lookup = match &answer {
+            CachedAnswer::Found(addrs) => Ok(DnsLookup {
+                addrs: addrs.clone(),
+                elapsed,
+            }),
+            CachedAnswer::NotFound => Err(DnsError::NotFound(host.clone())),
+        };
+        self.store(host, answer, valid_until);
+        lookup
+    }
+
+    fn cached(&self, host: &str) -> Option<Result<DnsLookup, DnsError>> {
+        let cache = self.cache.lock().unwrap();
+        let entry = cache.get(host).filter(|entry| entry.expires > Instant::now())?;
+        Some(match &entry.answer {
+            CachedAnswer::Found(addrs) => Ok(DnsLookup {
+                addrs: addrs.clone(),
+                elapsed: Duration::ZERO,
+            }),
+            CachedAnswer::NotFound => Err(DnsError::NotFound(host.to_string())),
+        })
+    }
+
+    /// Cache an answer until `valid_until`, the records' expiry, within the TTL limits
+    fn store(&self, host: String, answer: CachedAnswer, valid_until: Option<Instant>) {
+        let ttl = match answer {
+            CachedAnswer::Found(_) => POSITIVE_TTL_SECS,
+            CachedAnswer::NotFound => NEGATIVE_TTL_SECS,
+        };
+        let max_expires = Instant::now() + Duration::from_secs(ttl);
+        let expires = valid_until.map_or(max_expires, |valid_until| valid_until.min(max_expires));
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_ENTRIES && !cache.contains_key(&host) {
+            let now = Instant::now();
+            cache.retain(|_, entry| entry.expires > now);
+            if cache.len() >= MAX_ENTRIES {
+                cache.clear();
+            }
+        }
+        cache.insert(
+            host,
+            CacheEntry {
+                answer,
+                expires,
+            },
+        );
+    }
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let lookup = resolver.lookup(name.as_str()).await?;
+            let addrs: Addrs = Box::new(lookup.addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+// Clones share the cache
+impl Clone for CachingResolver {
+    fn clone(&self) -> Self {
+        Self {
+            resolver: self.resolver.clone(),
+            cache: self.cache.clone(),
+            guard: self.guard.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DnsError {
+    /// The name does not exist (NXDOMAIN) or has no addresses
+    NotFound(String),
+    /// Every address of the host is in a blocked range
+    Blocked(String),
+    Failed(String, String),
+}
+
+impl std::fmt::Display for DnsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DnsError::NotFound(host) => write!(f, "Host not found: {}", host),
+            DnsError::Blocked(host) => write!(f, "Host resolves to a blocked address: {}", host),
+            DnsError::Failed(host, e) => write!(f, "DNS lookup failed for {}: {}", host, e),
+        }
+    }
+}
+
+impl std::error::Error for DnsError {}
@@ -0,0 +1,68 @@
+mod wikipedia;
+
+use crate::parser::ParsedHtml;
+use lol_html::{ElementContentHandlers, Selector};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::sync::Arc;
+use url::Url;
+
+pub use wikipedia::{WikipediaExtractor, WikipediaFields};
+
+/// A lol_html handler for the elements matching a selector, as built by `element!` and `text!`
+pub type SelectorHandler<'h> = (Cow<'static, Selector>, ElementContentHandlers<'h>);
+
+/// Extraction rules for one site or family of pages, picked by the page URL
+pub trait Extractor: Send + Sync {
+    /// Whether the rules apply to a page, usually by its host and path
+    fn matches(&self, url: &Url) -> bool;
+
+    /// Fresh state for extracting a single page
+    fn page(&self, url: &Url) -> Box<dyn PageExtractor>;
+}
+
+/// The extraction of one page: handlers that run alongside the generic ones, then the fields they found
+pub trait PageExtractor {
+    /// Handlers to add to the parser; for the same element they run after the generic handlers
+    fn handlers(&self) -> Vec<SelectorHandler<'_>>;
+
+    /// Write the extracted fields into the record once the whole page was parsed
+    fn finish(self: Box<Self>, parsed: &mut ParsedHtml);
+}
+
+/// Fields added by a site-specific extractor, tagged with the extractor's name
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "extractor", rename_all = "lowercase")]
+pub enum SiteFields {
+    Wikipedia(WikipediaFields),
+}
+
+/// Registry of site-specific extractors
+pub struct Extractors {
+    extractors: Vec<Arc<dyn Extractor>>,
+}
+
+impl Extractors {
+    pub fn new() -> Self {
+        Self {
+            extractors: Vec::new(),
+        }
+    }
+
+    /// Registry with the built-in Wikipedia extractor
+    pub fn with_defaults() -> Self {
+        let mut extractors = Self::new();
+        extractors.register(Arc::new(WikipediaExtractor));
+        extractors
+    }
+
+    /// Register an extractor; it takes precedence over earlier ones matching the same pages
+    pub fn register(&mut self, extractor: Arc<dyn Extractor>) {
+        self.extractors.push(extractor);
+    }
+
+    /// The extractor for a page, or None when only the generic extraction applies
+    pub fn find(&self, url: &Url) -> Option<&dyn Extractor> {
+        self.extractors.iter().rev().find(|extractor| extractor.matches(url)).map(|extractor| extractor.as_ref())
+    }
+}
@@ -0,0 +1,320 @@
+use super::{Extractor, PageExtractor, SelectorHandler, SiteFields};
+use crate::normalize::{collapse_whitespace, decode_attribute, decode_entities};
+use crate::parser::ParsedHtml;
+use lol_html::{element, text};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use url::Url;
+
+const ARTICLE_PATH: &str = "/wiki/";
+const SEE_ALSO_ID: &str = "See_also";
+
+/// Infobox text that isn't part of a value: inline styles, footnote markers and edit links
+const INFOBOX_NOISE_SELECTOR: &str = "table.infobox style, table.infobox sup.reference, table.infobox .noprint";
+/// Elements that separate the items of an infobox value
+const INFOBOX_BREAK_SELECTOR: &str = "table.infobox br, table.infobox li, table.infobox p, table.infobox div";
+/// Page parts that come after the article text and end a trailing See also section
+const ARTICLE_END_SELECTOR: &str = "#catlinks, .navbox, .printfooter";
+/// Disambiguation notices, in the rendered page and in Parsoid HTML
+const DISAMBIGUATION_SELECTOR: &str = r#"#disambigbox, .dmbox-disambig, meta[property="mw:PageProp/disambiguation"]"#;
+
+/// Wikipedia articles, on any language edition and on the mobile site
+pub struct WikipediaExtractor;
+
+impl Extractor for WikipediaExtractor {
+    fn matches(&self, url: &Url) -> bool {
+        let host = url.host_str().unwrap_or_default();
+        (host == "wikipedia.org" || host.ends_with(".wikipedia.org")) && url.path().starts_with(ARTICLE_PATH)
+    }
+
+    fn page(&self, url: &Url) -> Box<dyn PageExtractor> {
+        Box::new(WikipediaPage::new(url.clone()))
+    }
+}
+
+/// What a Wikipedia article adds to the generic record
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct WikipediaFields {
+    /// Visible categories, without the `Category:` prefix
+    pub categories: Vec<String>,
+    /// Labelled rows of the infoboxes, in page order
+    pub infobox: Vec<InfoboxField>,
+    /// Articles listed in the See also section
+    pub see_also: Vec<String>,
+    /// Whether the page only lists articles with similar titles
+    pub disambiguation: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct InfoboxField {
+    pub key: String,
+    pub value: String,
+}
+
+/// Infobox cell the text chunks are going to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum InfoboxCell {
+    Key,
+    Value,
+}
+
+/// State of one article; chunks are kept raw until the page is done, as an entity may span chunks
+struct WikipediaPage {
+    page_url: Url,
+    categories: RefCell<Vec<String>>,
+    /// Raw key and value of each infobox row
+    rows: RefCell<Vec<(String, String)>>,
+    cell: Cell<Option<InfoboxCell>>,
+    /// Set for a chunk of infobox noise before it reaches the row collector
+    skip_chunk: Cell<bool>,
+    in_see_also: Cell<bool>,
+    see_also: RefCell<Vec<String>>,
+    disambiguation: Cell<bool>,
+}
+
+impl WikipediaPage {
+    fn new(page_url: Url) -> Self {
+        Self {
+            page_url,
+            categories: RefCell::new(Vec::new()),
+            rows: RefCell::new(Vec::new()),
+            cell: Cell::new(None),
+            skip_chunk: Cell::new(false),
+            in_see_also: Cell::new(false),
+            see_also: RefCell::new(Vec::new()),
+            disambiguation: Cell::new(false),
+        }
+    }
+
+    fn push_cell(&self, chunk: &str) {
+        let mut rows = self.rows.borrow_mut();
+        match (self.cell.get(), rows.last_mut()) {
+            (Some(InfoboxCell::Key), Some((key, _))) => key.push_str(chunk),
+            (Some(InfoboxCell::Value), Some((_, value))) => value.push_str(chunk),
+            _ => {}
+        }
+    }
+
+    /// An article linked from the See also section; other namespaces such as `Category:` are left out
+    fn see_also_link(&self, href: &str) -> Option<String> {
+        let href = decode_attribute(href);
+        if href.trim().starts_with('#') {
+            return None;
+        }
+        let mut url = self.page_url.join(href.trim()).ok()?;
+        let title = url.path().strip_prefix(ARTICLE_PATH)?;
+        if title.is_empty() || title.contains(':') || url.host_str() != self.page_url.host_str() {
+            return None;
+        }
+        url.set_fragment(None);
+        Some(url.to_string())
+    }
+}
+
+impl PageExtractor for WikipediaPage {
+    fn handlers(&self) -> Vec<SelectorHandler<'_>> {
+        vec![
+            // Categories
+            element!("#mw-normal-catlinks li a", |_| {
+                self.categories.borrow_mut().push(String::new());
+                Ok(())
+            }),
+            text!("#mw-normal-catlinks li a", |t| {
+                if let Some(category) = self.categories.borrow_mut().last_mut() {
+                    category.push_str(t.as_str());
+                }
+                Ok(())
+            }),
+            // Disambiguation pages carry a notice or sit in a disambiguation category, hidden ones included
+            element!(DISAMBIGUATION_SELECTOR, |_| {
+                self.disambiguation.set(true);
+                Ok(())
+            }),
+            element!("#catlinks a[title]", |el| {
+                let title = el.get_attribute("title").unwrap_or_default().to_ascii_lowercase();
+                if title.starts_with("category:") && title.ends_with("disambiguation pages") {
+                    self.disambiguation.set(true);
+                }
+                Ok(())
+            }),
+            // Infobox rows, a label cell and a data cell each
+            element!("table.infobox tr", |_| {
+                self.rows.borrow_mut().push((String::new(), String::new()));
+                self.cell.set(None);
+                Ok(())
+            }),
+            element!("table.infobox th", |_| {
+                self.cell.set(Some(InfoboxCell::Key));
+                Ok(())
+            }),
+            element!("table.infobox td", |_| {
+                self.cell.set(Some(InfoboxCell::Value));
+                Ok(())
+            }),
+            element!(INFOBOX_BREAK_SELECTOR, |_| {
+                self.push_cell(" ");
+                Ok(())
+            }),
+            text!(INFOBOX_NOISE_SELECTOR, |_| {
+                self.skip_chunk.set(true);
+                Ok(())
+            }),
+            text!("table.infobox", |t| {
+                if !self.skip_chunk.replace(false) {
+                    self.push_cell(t.as_str());
+                }
+                Ok(())
+            }),
+            // See also runs from its heading to the next section or the end of the article
+            element!("h2", |el| {
+                let id = el.get_attribute("id");
+                self.in_see_also.set(id.as_deref() == Some(SEE_ALSO_ID));
+                Ok(())
+            }),
+            // Older markup puts the section id on a span inside the heading
+            element!("h2 .mw-headline[id]", |el| {
+                if el.get_attribute("id").as_deref() == Some(SEE_ALSO_ID) {
+                    self.in_see_also.set(true);
+                }
+                Ok(())
+            }),
+            element!(ARTICLE_END_SELECTOR, |_| {
+                self.in_see_also.set(false);
+                Ok(())
+            }),
+            element!("li a[href]", |el| {
+                if self.in_see_also.get()
+                    && let Some(url) = el.get_attribute("href").and_then(|href| self.see_also_link(&href)) {
+                    self.see_also.borrow_mut().push(url);
+                }
+                Ok(())
+            }),
+        ]
+    }
+
+    fn finish(self: Box<Self>, parsed: &mut ParsedHtml) {
+        let page = *self;
+        let clean = |raw: &str| collapse_whitespace(&decode_entities(raw));
+
+        let mut categories: Vec<String> = Vec::new();
+        for category in page.categories.into_inner() {
+            let category = clean(&category);
+            if !category.is_empty() && !categories.contains(&category) {
+                categories.push(category);
+            }
+        }
+
+        let infobox = page
+            .rows
+            .into_inner()
+            .iter()
+            .map(|(key, value)| InfoboxField {
+                key: clean(key),
+                value: clean(value),
+            })
+            .filter(|field| !field.key.is_empty() && !field.value.is_empty())
+            .collect();
+
+        let mut see_also = page.see_also.into_inner();
+        let mut seen = HashSet::new();
+        see_also.retain(|url| seen.insert(url.clone()));
+
+        parsed.site = Some(SiteFields::Wikipedia(WikipediaFields {
+            categories,
+            infobox,
+            see_also,
+            disambiguation: page.disambiguation.get(),
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::Extractors;
+    use crate::parser::parse_html;
+
+    const ARTICLE_URL: &str = "https://en.wikipedia.org/wiki/Full-text_search";
+
+    fn fields(html: &str, url: &str) -> Option<WikipediaFields> {
+        let parsed = parse_html(html.to_string(), url, &Extractors::with_defaults()).unwrap();
+        parsed.site.map(|SiteFields::Wikipedia(fields)| fields)
+    }
+
+    const ARTICLE: &str = r##"<html><body><div id="mw-content-text">
+        <table class="infobox">
+            <tr><th colspan="2">Full-text search</th></tr>
+            <tr><th>Type</th><td>Information retrieval<sup class="reference">[1]</sup></td></tr>
+            <tr><th>Uses</th><td><ul><li>Search engines</li><li>Databases</li></ul></td></tr>
+            <tr><th>Developer <span class="noprint">[edit]</span></th><td>Various &amp; others<style>.x{}</style></td></tr>
+        </table>
+        <p>Full-text search is a technique.</p>
+        <h2 id="See_also">See also</h2>
+        <ul>
+            <li><a href="/wiki/Search_engine">Search engine</a></li>
+            <li><a href="/wiki/Index_(search_engine)#Inverted">Index</a></li>
+            <li><a href="/wiki/Search_engine">Search engine again</a></li>
+            <li><a href="/wiki/Category:Search">Category</a></li>
+            <li><a href="#References">References</a></li>
+            <li><a href="https://de.wikipedia.org/wiki/Volltextsuche">German</a></li>
+        </ul>
+        <h2 id="References">References</h2>
+        <ul><li><a href="/wiki/Not_see_also">Reference</a></li></ul>
+    </div>
+    <div id="catlinks"><div id="mw-normal-catlinks"><ul>
+        <li><a href="/wiki/Category:Text_search" title="Category:Text search">Text search</a></li>
+        <li><a href="/wiki/Category:Information_retrieval" title="Category:Information retrieval">Information retrieval</a></li>
+        <li><a href="/wiki/Category:Text_search">Text search</a></li>
+    </ul></div></div></body></html>"##;
+
+    #[test]
+    fn extracts_article_fields() {
+        let fields = fields(ARTICLE, ARTICLE_URL).unwrap();
+        assert_eq!(fields.categories, ["Text search", "Information retrieval"]);
+        assert_eq!(
+            fields.infobox,
+            [
+                InfoboxField { key: "Type".to_string(), value: "Information retrieval".to_string() },
+                InfoboxField { key: "Uses".to_string(), value: "Search engines Databases".to_string() },
+                InfoboxField { key: "Developer".to_string(), value: "Various & others".to_string() },
+            ]
+        );
+        assert_eq!(
+            fields.see_also,
+            ["https://en.wikipedia.org/wiki/Search_engine", "https://en.wikipedia.org/wiki/Index_(search_engine)"]
+        );
+        assert!(!fields.disambiguation);
+    }
+
+    #[test]
+    fn see_also_ends_at_the_article_end() {
+        let html = r#"<h2><span class="mw-headline" id="See_also">See also</span></h2>
+            <ul><li><a href="/wiki/Inverted_index">Inverted index</a></li></ul>
+            <div class="navbox"><ul><li><a href="/wiki/Navigation">Navigation</a></li></ul></div>"#;
+        assert_eq!(fields(html, ARTICLE_URL).unwrap().see_also, ["https://en.wikipedia.org/wiki/Inverted_index"]);
+    }
+
+    #[test]
+    fn detects_disambiguation_pages() {
+        let notice = r#"<div id="disambigbox">This disambiguation page lists articles</div>"#;
+        assert!(fields(notice, "https://en.wikipedia.org/wiki/Mercury").unwrap().disambiguation);
+        let category = r#"<div id="catlinks"><div id="mw-hidden-catlinks"><ul>
+            <li><a href="/wiki/Category:All_disambiguation_pages" title="Category:All disambiguation pages">All</a></li>
+        </ul></div></div>"#;
+        let fields = fields(category, "https://en.wikipedia.org/wiki/Mercury").unwrap();
+        assert!(fields.disambiguation);
+        assert!(fields.categories.is_empty());
+    }
+
+    #[test]
+    fn only_matches_articles() {
+        let extractor = WikipediaExtractor;
+        let matches = |url: &str| extractor.matches(&Url::parse(url).unwrap());
+        assert!(matches("https://en.m.wikipedia.org/wiki/Search"));
+        assert!(matches("https://wikipedia.org/wiki/Search"));
+        assert!(!matches("https://en.wikipedia.org/w/index.php?title=Search"));
+        assert!(!matches("https://notwikipedia.org/wiki/Search"));
+        assert_eq!(fields("<p>Text</p>", "https://en.wikipedia.org/w/index.php?title=Search"), None);
+    }
+}
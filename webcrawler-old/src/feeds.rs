@@ -0,0 +1,246 @@
+use crate::http_client::HttpClient;
+use crate::parser;
+use crate::rate_limiter::RateLimiter;
+use crate::url_store::UrlStore;
+use chrono::DateTime;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use url::Url;
+
+const MAX_FEED_SIZE: usize = 5 * 1024 * 1024;
+const POLL_TICK_SECS: u64 = 30;
+const MAX_FEEDS_PER_TICK: usize = 100;
+pub const INITIAL_FEED_INTERVAL_SECS: u64 = 15 * 60;
+const MIN_FEED_INTERVAL_SECS: u64 = 5 * 60;
+const MAX_FEED_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
+/// Frontier priority for links found in feeds, above any sitemap priority
+pub const FEED_PRIORITY: u8 = 200;
+
+/// Content types advertised by `<link rel="alternate">` feed links
+pub const FEED_CONTENT_TYPES: [&str; 3] = [
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/rdf+xml",
+];
+
+/// A single RSS item or Atom entry
+#[derive(Debug, Clone)]
+pub struct FeedEntry {
+    pub link: String,
+    pub updated: Option<u64>,
+}
+
+/// Parse an RSS 2.0, RSS 1.0 (RDF) or Atom feed into its entries
+/// Relative entry links are resolved against the feed URL
+pub fn parse_feed(body: &[u8], feed_url: &str) -> Option<Vec<FeedEntry>> {
+    let base = Url::parse(feed_url).ok()?;
+    let text = String::from_utf8_lossy(body);
+    let doc = roxmltree::Document::parse(text.trim_start_matches('\u{feff}').trim_start()).ok()?;
+    let root = doc.root_element();
+
+    let resolve = |href: &str| base.join(href.trim()).ok().map(|u| u.to_string());
+
+    let entries = match root.tag_name().name() {
+        // Atom: <feed><entry><link rel="alternate" href="..."/></entry></feed>
+        "feed" => root
+            .children()
+            .filter(|node| node.tag_name().name() == "entry")
+            .filter_map(|entry| {
+                let link = entry
+                    .children()
+                    .filter(|child| child.tag_name().name() == "link")
+                    .find(|link| link.attribute("rel").is_none_or(|rel| rel == "alternate"))
+                    .and_then(|link| link.attribute("href"))
+                    .and_then(resolve)?;
+                let updated = ["updated", "published"].iter().find_map(|name| {
+                    child_text(entry, name).and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+                });
+                Some(FeedEntry {
+                    link,
+                    updated: updated.and_then(|date| u64::try_from(date.timestamp()).ok()),
+                })
+            })
+            .collect(),
+        // RSS 2.0 nests items in <channel>, RSS 1.0 puts them next to it
+        "rss" | "RDF" => root
+            .descendants()
+            .filter(|node| node.tag_name().name() == "item")
+            .filter_map(|item| {
+                let link = child_text(item, "link").and_then(resolve)?;
+                let updated = child_text(item, "pubDate")
+                    .and_then(|date| DateTime::parse_from_rfc2822(date).ok())
+                    .or_else(|| {
+                        child_text(item, "date").and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+                    });
+                Some(FeedEntry {
+                    link,
+                    updated: updated.and_then(|date| u64::try_from(date.timestamp()).ok()),
+                })
+            })
+            .collect(),
+        _ => return None,
+    };
+
+    Some(entries)
+}
+
+fn child_text<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|child| child.tag_name().name() == name)
+        .and_then(|child| child.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+}
+
+/// Whether two URLs are on the same host
+pub fn same_host(url: &str, other: &str) -> bool {
+    let host = |url: &str| Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_string));
+    host(url).is_some_and(|url_host| host(other) == Some(url_host))
+}
+
+/// Next poll interval: poll faster while a feed keeps producing new links, back off when it doesn't
+pub fn next_interval(current: u64, new_links: usize) -> u64 {
+    let next = if new_links > 0 { current / 2 } else { current * 2 };
+    next.clamp(MIN_FEED_INTERVAL_SECS, MAX_FEED_INTERVAL_SECS)
+}
+
+/// Periodically re-fetches known feeds and pushes their entries to the front of the frontier
+pub struct FeedPoller {
+    http_client: Arc<HttpClient>,
+    rate_limiter: RateLimiter,
+    url_store: UrlStore,
+    urls_added: Arc<AtomicUsize>,
+}
+
+impl FeedPoller {
+    pub fn new(
+        http_client: Arc<HttpClient>,
+        rate_limiter: RateLimiter,
+        url_store: UrlStore,
+        urls_added: Arc<AtomicUsize>,
+    ) -> Self {
+        Self {
+            http_client,
+            rate_limiter,
+            url_store,
+            urls_added,
+        }
+    }
+
+    /// Run the polling loop - call this in a tokio task
+    pub async fn run(self) {
+        loop {
+            for (feed_url, interval) in self.url_store.due_feeds(MAX_FEEDS_PER_TICK) {
+                let new_links = self.poll(&feed_url).await;
+                let interval = next_interval(interval, new_links.unwrap_or(0));
+                self.url_store.schedule_feed(&feed_url, interval);
+            }
+
+            tokio::time::sleep(Duration::from_secs(POLL_TICK_SECS)).await;
+        }
+    }
+
+    /// Fetch one feed and enqueue its entries, returning how many were new to the frontier
+    async fn poll(&self, feed_url: &str) -> Option<usize> {
+        self.rate_limiter.wait_if_needed(feed_url).await;
+        let body = self.http_client.fetch_bytes(feed_url, MAX_FEED_SIZE).await.ok()?;
+        let entries = parse_feed(&body, feed_url)?;
+
+        let mut new_links = 0;
+        for entry in entries {
+            // A feed may only push URLs of its own host to the front of the frontier
+            if !same_host(&entry.link, feed_url) || !parser::is_crawlable(&entry.link) {
+                continue;
+            }
+
+            let added = match entry.updated {
+                Some(updated) => self.url_store.requeue_if_modified(&entry.link, updated, FEED_PRIORITY),
+                None => self.url_store.add_to_frontier_with_priority(&entry.link, FEED_PRIORITY),
+            };
+            if added {
+                new_links += 1;
+            }
+        }
+
+        self.urls_added.fetch_add(new_links, Ordering::Relaxed);
+        Some(new_links)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEED_URL: &str = "https://en.wikipedia.org/feeds/news.xml";
+
+    fn links(entries: &[FeedEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.link.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_rss() {
+        let rss = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel>
+  <title>News</title>
+  <item><link>https://en.wikipedia.org/wiki/A</link><pubDate>Wed, 31 Jan 2024 12:00:00 GMT</pubDate></item>
+  <item><link>/wiki/B</link></item>
+  <item><title>No link</title></item>
+</channel></rss>"#;
+        let entries = parse_feed(rss.as_bytes(), FEED_URL).unwrap();
+        assert_eq!(links(&entries), ["https://en.wikipedia.org/wiki/A", "https://en.wikipedia.org/wiki/B"]);
+        assert_eq!(entries[0].updated, Some(1_706_702_400));
+        assert_eq!(entries[1].updated, None);
+    }
+
+    #[test]
+    fn parses_atom() {
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom">
+  <entry>
+    <link rel="edit" href="/edit/A"/>
+    <link rel="alternate" href="/wiki/A"/>
+    <updated>2024-01-31T12:00:00Z</updated>
+  </entry>
+  <entry><link href="https://en.wikipedia.org/wiki/B"/><published>2024-01-31T14:00:00+02:00</published></entry>
+</feed>"#;
+        let entries = parse_feed(atom.as_bytes(), FEED_URL).unwrap();
+        assert_eq!(links(&entries), ["https://en.wikipedia.org/wiki/A", "https://en.wikipedia.org/wiki/B"]);
+        assert_eq!(entries[0].updated, Some(1_706_702_400));
+        assert_eq!(entries[1].updated, Some(1_706_702_400));
+    }
+
+    #[test]
+    fn parses_rdf() {
+        let rdf = r#"<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#" xmlns="http://purl.org/rss/1.0/" xmlns:dc="http://purl.org/dc/elements/1.1/">
+  <channel rdf:about="https://en.wikipedia.org/"><title>News</title></channel>
+  <item rdf:about="https://en.wikipedia.org/wiki/A"><link>https://en.wikipedia.org/wiki/A</link><dc:date>2024-01-31T12:00:00Z</dc:date></item>
+</rdf:RDF>"#;
+        let entries = parse_feed(rdf.as_bytes(), FEED_URL).unwrap();
+        assert_eq!(links(&entries), ["https://en.wikipedia.org/wiki/A"]);
+        assert_eq!(entries[0].updated, Some(1_706_702_400));
+    }
+
+    #[test]
+    fn rejects_other_documents() {
+        assert!(parse_feed(b"<html><body></body></html>", FEED_URL).is_none());
+        assert!(parse_feed(b"not xml", FEED_URL).is_none());
+    }
+
+    #[test]
+    fn interval_stays_between_limits() {
+        assert_eq!(next_interval(INITIAL_FEED_INTERVAL_SECS, 3), INITIAL_FEED_INTERVAL_SECS / 2);
+        assert_eq!(next_interval(INITIAL_FEED_INTERVAL_SECS, 0), INITIAL_FEED_INTERVAL_SECS * 2);
+        assert_eq!(next_interval(MIN_FEED_INTERVAL_SECS, 10), MIN_FEED_INTERVAL_SECS);
+        assert_eq!(next_interval(MAX_FEED_INTERVAL_SECS, 0), MAX_FEED_INTERVAL_SECS);
+        assert_eq!(next_interval(0, 1), MIN_FEED_INTERVAL_SECS);
+    }
+
+    #[test]
+    fn same_host_compares_hosts() {
+        assert!(same_host("https://en.wikipedia.org/wiki/A", FEED_URL));
+        assert!(same_host("http://en.wikipedia.org/wiki/A", FEED_URL));
+        assert!(!same_host("https://de.wikipedia.org/wiki/A", FEED_URL));
+        assert!(!same_host("not a url", FEED_URL));
+    }
+}
@@ -1,61 +1,399 @@
-use reqwest::Client;
-use std::time::Duration;
+use crate::circuit_breaker::HostCircuitBreaker;
+use crate::config::CrawlerConfig;
+use crate::dns::{CachingResolver, DnsError};
+use crate::probe::{self, ContentTypeCache, Verdict};
+use crate::profiles::RequestProfiles;
+use crate::proxy::ProxyPool;
+use crate::robots;
+use crate::sniff;
+use crate::ssrf::AddressGuard;
+use reqwest::header::{HeaderName, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, LOCATION, RANGE, X_CONTENT_TYPE_OPTIONS};
+use reqwest::redirect::Policy;
+use reqwest::{Client, ClientBuilder, RequestBuilder, Response, StatusCode};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use url::{Host, Url};
 
-const MAX_RESPONSE_SIZE: usize = 10 * 1024 * 1024;
+const MAX_REDIRECTS: usize = 10;
+
+/// A successful response body with its declared content type
+#[derive(Debug, Clone)]
+pub struct FetchedContent {
+    /// Lowercase MIME essence, e.g. `text/html`
+    pub mime_type: String,
+    pub charset: Option<String>,
+    pub body: Vec<u8>,
+    /// Time spent resolving the host and those of any redirects, None when the request went through a proxy
+    pub dns_time: Option<Duration>,
+    /// Directives from X-Robots-Tag headers that apply to this crawler
+    pub robots: Vec<String>,
+}
 
 pub struct HttpClient {
     client: Client,
+    proxies: Option<Arc<ProxyPool>>,
+    resolver: CachingResolver,
+    guard: Arc<AddressGuard>,
+    hosts: HostCircuitBreaker,
+    profiles: RequestProfiles,
+    content_types: ContentTypeCache,
+    head_probing: bool,
 }
 
 impl HttpClient {
-    pub fn new() -> Result<Self, reqwest::Error> {
-        let client = Client::builder()
+    pub fn new(config: &CrawlerConfig) -> Result<Self, reqwest::Error> {
+        let guard = Arc::new(AddressGuard::new(&config.private_allowlist));
+        let resolver = CachingResolver::new(guard.clone());
+        let client = Self::client_builder(&resolver).build()?;
+        
+        let proxies = if config.proxies.is_empty() {
+            None
+        } else {
+            let builder = || Self::client_builder(&resolver);
+            Some(Arc::new(ProxyPool::new(&config.proxies, &config.proxy_check_url, builder)?))
+        };
+        
+        Ok(Self {
+            client,
+            proxies,
+            resolver,
+            guard,
+            hosts: HostCircuitBreaker::new(),
+            profiles: RequestProfiles::new(&config.profiles),
+            content_types: ContentTypeCache::new(),
+            head_probing: config.head_probing,
+        })
+    }
+    
+    /// Settings shared by the direct client and every proxy client
+    /// Redirects are followed by `send`, which checks each hop and applies its host's profile
+    fn client_builder(resolver: &CachingResolver) -> ClientBuilder {
+        Client::builder()
             .timeout(Duration::from_secs(30))
             .connect_timeout(Duration::from_secs(30))
             .user_agent("Mozilla/5.0 (compatible; WebCrawler/1.0)")
             .pool_max_idle_per_host(10)
-            .build()?;
-        
-        Ok(Self { client })
+            .dns_resolver(Arc::new(resolver.clone()))
+            .redirect(Policy::none())
     }
     
-    pub async fn fetch(&self, url: &str) -> Result<String, FetchError> {
-        let response = self.client.get(url).send().await?;
+    pub fn proxy_pool(&self) -> Option<Arc<ProxyPool>> {
+        self.proxies.clone()
+    }
+    
+    pub fn profiles(&self) -> &RequestProfiles {
+        &self.profiles
+    }
+    
+    /// Remaining cool-down of a URL's host if it has been found unreachable
+    pub fn host_down_for(&self, url: &str) -> Option<Duration> {
+        let host = Url::parse(url).ok()?.host_str()?.to_string();
+        self.hosts.open_for(&host)
+    }
+    
+    /// Fetch a page, accepting only content types for which `max_size_for` returns a limit
+    /// Rejected types are detected as early as possible: from what the URL pattern served before,
+    /// from a HEAD probe for suspect URLs, or from the headers before the body is read
+    pub async fn fetch(
+        &self,
+        url: &str,
+        max_size_for: impl Fn(&str) -> Option<usize>,
+    ) -> Result<FetchedContent, FetchError> {
+        match self.content_types.verdict(url) {
+            Verdict::Skip(mime_type) => return Err(FetchError::Skipped(mime_type)),
+            // The pattern has always served accepted types, so suspect URLs go straight to the GET
+            Verdict::Fetch => {}
+            Verdict::Unknown if self.head_probing && probe::is_suspect(url) => {
+                if let Some((content_type, content_length)) = self.probe(url).await
+                    && !sniff::is_sniffable(Some(&content_type), false) {
+                    let (mime_type, _) = parse_content_type(&content_type);
+                    if max_size_for(&mime_type).is_none() {
+                        self.content_types.record(url, &mime_type, false);
+                        return Err(FetchError::InvalidContentType {
+                            content_type,
+                            skipped_bytes: content_length,
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+        
+        let (mut response, dns_time) = self.send(url, |client, url| client.get(url)).await?;
         
         let status = response.status();
         if !status.is_success() {
             return Err(FetchError::HttpError(status.as_u16()));
         }
         
-        if let Some(content_type) = response.headers().get("content-type") {
-            let content_type_str = content_type.to_str().unwrap_or("");
-            if !content_type_str.contains("text/html") {
-                return Err(FetchError::InvalidContentType(content_type_str.to_string()));
+        let content_type = header_str(&response, CONTENT_TYPE);
+        let no_sniff = header_str(&response, X_CONTENT_TYPE_OPTIONS)
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case("nosniff"));
+        let charset = content_type.as_deref().and_then(|ct| parse_content_type(ct).1);
+        let declared_length = response.content_length();
+        let robots_directives: Vec<String> = response
+            .headers()
+            .get_all("x-robots-tag")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(robots::header_directives)
+            .collect();
+        
+        // Declared types are checked before any of the body is read
+        if !sniff::is_sniffable(content_type.as_deref(), no_sniff) {
+            let content_type = content_type.clone().unwrap_or_default();
+            let (mime_type, _) = parse_content_type(&content_type);
+            if max_size_for(&mime_type).is_none() {
+                self.content_types.record(url, &mime_type, false);
+                return Err(FetchError::InvalidContentType {
+                    content_type,
+                    skipped_bytes: declared_length,
+                });
             }
         }
         
-        if let Some(content_length) = response.content_length()
-            && content_length > MAX_RESPONSE_SIZE as u64 {
+        // Read just enough to sniff, so unwanted bodies are abandoned after the first chunk
+        let mut body = Vec::new();
+        while body.len() < sniff::RESOURCE_HEADER_SIZE
+            && let Some(chunk) = response.chunk().await? {
+            body.extend_from_slice(&chunk);
+        }
+        
+        let mime_type = sniff::sniff_mime_type(content_type.as_deref(), no_sniff, &body);
+        let Some(max_size) = max_size_for(&mime_type) else {
+            self.content_types.record(url, &mime_type, false);
+            return Err(FetchError::InvalidContentType {
+                content_type: mime_type,
+                skipped_bytes: declared_length.map(|length| length.saturating_sub(body.len() as u64)),
+            });
+        };
+        self.content_types.record(url, &mime_type, true);
+        
+        if let Some(content_length) = declared_length
+            && content_length > max_size as u64 {
             return Err(FetchError::TooLarge(content_length));
         }
+        read_body(&mut response, &mut body, max_size).await?;
+        
+        Ok(FetchedContent {
+            mime_type,
+            charset,
+            body,
+            dns_time,
+            robots: robots_directives,
+        })
+    }
+    
+    /// Fetch a raw response body of any content type, up to `max_size` bytes
+    pub async fn fetch_bytes(&self, url: &str, max_size: usize) -> Result<Vec<u8>, FetchError> {
+        let (mut response, _) = self.send(url, |client, url| client.get(url)).await?;
         
-        let body = response.text().await?;
-        if body.len() > MAX_RESPONSE_SIZE {
-            return Err(FetchError::TooLarge(body.len() as u64));
+        let status = response.status();
+        if !status.is_success() {
+            return Err(FetchError::HttpError(status.as_u16()));
         }
         
+        if let Some(content_length) = response.content_length()
+            && content_length > max_size as u64 {
+            return Err(FetchError::TooLarge(content_length));
+        }
+        
+        let mut body = Vec::new();
+        read_body(&mut response, &mut body, max_size).await?;
         Ok(body)
     }
+    
+    /// Send a request directly, or through the proxy assigned to each host, following redirects here rather than in reqwest
+    /// Every hop gets its own host's checks, profile and cookie jar, so headers and credentials never follow
+    /// a redirect to another host, and proxied hops are checked against the guard although the proxy resolves them
+    /// `request` builds the request for a URL, with the client to send it with
+    async fn send(
+        &self,
+        url: &str,
+        request: impl Fn(&Client, &str) -> RequestBuilder,
+    ) -> Result<(Response, Option<Duration>), FetchError> {
+        let mut url = url.to_string();
+        let mut redirects = 0;
+        let mut dns_time: Option<Duration> = None;
+        loop {
+            let Ok(parsed) = Url::parse(&url) else {
+                // reqwest rejects the URL before connecting
+                return Ok((request(&self.client, &url).send().await?, dns_time));
+            };
+            let host = parsed.host_str().unwrap_or_default();
+            let lookup_time = self.check_destination(&parsed).await?;
+            
+            let route = self.proxies.as_ref().and_then(|pool| Some((pool, pool.route(&url)?)));
+            let response = match route {
+                Some((pool, (index, client))) => {
+                    let started = Instant::now();
+                    let result = self.profiles.apply(host, request(&client, &url)).send().await;
+                    pool.record_result(index, &result, started.elapsed());
+                    result?
+                }
+                None => {
+                    if let Some(lookup_time) = lookup_time {
+                        dns_time = Some(dns_time.unwrap_or_default() + lookup_time);
+                    }
+                    match self.profiles.apply(host, request(&self.client, &url)).send().await {
+                        Ok(response) => {
+                            self.hosts.record_success(host);
+                            response
+                        }
+                        Err(e) => {
+                            if e.is_connect() || e.is_timeout() {
+                                self.hosts.record_failure(host);
+                            }
+                            return Err(e.into());
+                        }
+                    }
+                }
+            };
+            self.profiles.store_cookies(&response);
+            
+            // After too many redirects the last one is returned as is, and callers reject its status
+            match redirect_target(&parsed, &response) {
+                Some(next) if redirects < MAX_REDIRECTS => {
+                    redirects += 1;
+                    url = next.to_string();
+                }
+                _ => return Ok((response, dns_time)),
+            }
+        }
+    }
+    
+    /// Refuse hosts whose breaker is open or that are, or resolve to, blocked addresses
+    /// Returns the time spent resolving, so unreachable hosts also trip their breaker here
+    async fn check_destination(&self, parsed: &Url) -> Result<Option<Duration>, FetchError> {
+        let host = parsed.host_str().unwrap_or_default();
+        if self.hosts.open_for(host).is_some() {
+            return Err(FetchError::HostUnavailable(host.to_string()));
+        }
+        if self.guard.blocks_url(parsed) {
+            return Err(FetchError::Dns(DnsError::Blocked(host.to_string())));
+        }
+        
+        let Some(Host::Domain(domain)) = parsed.host() else {
+            return Ok(None);
+        };
+        match self.resolver.lookup(domain).await {
+            Ok(lookup) => Ok(Some(lookup.elapsed)),
+            Err(e) => {
+                if !matches!(e, DnsError::Blocked(_)) {
+                    self.hosts.record_failure(host);
+                }
+                Err(FetchError::Dns(e))
+            }
+        }
+    }
+    
+    /// Find a URL's content type and size without downloading it
+    /// Uses HEAD, or a one-byte ranged GET for servers that don't support HEAD
+    async fn probe(&self, url: &str) -> Option<(String, Option<u64>)> {
+        let (head, _) = self.send(url, |client, url| client.head(url)).await.ok()?;
+        if head.status().is_success() {
+            let content_length = header_str(&head, CONTENT_LENGTH).and_then(|length| length.parse().ok());
+            return Some((header_str(&head, CONTENT_TYPE)?, content_length));
+        }
+        if !matches!(head.status(), StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED) {
+            return None;
+        }
+        
+        let (ranged, _) = self
+            .send(url, |client, url| client.get(url).header(RANGE, "bytes=0-0"))
+            .await
+            .ok()?;
+        let content_type = header_str(&ranged, CONTENT_TYPE)?;
+        let content_length = match ranged.status() {
+            // Content-Range carries the full size: "bytes 0-0/12345"
+            StatusCode::PARTIAL_CONTENT => header_str(&ranged, CONTENT_RANGE)
+                .and_then(|range| range.rsplit('/').next()?.parse().ok()),
+            // Servers that ignore Range send the whole body, which is dropped unread
+            StatusCode::OK => ranged.content_length(),
+            _ => return None,
+        };
+        Some((content_type, content_length))
+    }
+}
+
+/// Append the rest of a response body, aborting as soon as it exceeds `max_size`
+async fn read_body(response: &mut Response, body: &mut Vec<u8>, max_size: usize) -> Result<(), FetchError> {
+    if body.len() > max_size {
+        return Err(FetchError::TooLarge(body.len() as u64));
+    }
+    while let Some(chunk) = response.chunk().await? {
+        if body.len() + chunk.len() > max_size {
+            return Err(FetchError::TooLarge((body.len() + chunk.len()) as u64));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(())
+}
+
+/// Where a redirect response points, for the statuses reqwest would follow
+fn redirect_target(url: &Url, response: &Response) -> Option<Url> {
+    let followed = matches!(
+        response.status(),
+        StatusCode::MOVED_PERMANENTLY
+            | StatusCode::FOUND
+            | StatusCode::SEE_OTHER
+            | StatusCode::TEMPORARY_REDIRECT
+            | StatusCode::PERMANENT_REDIRECT
+    );
+    if !followed {
+        return None;
+    }
+    url.join(&header_str(response, LOCATION)?).ok()
+}
+
+fn header_str(response: &Response, name: HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
+/// Split a content-type header into its lowercase MIME essence and charset parameter
+fn parse_content_type(content_type: &str) -> (String, Option<String>) {
+    let mut parts = content_type.split(';');
+    let mime_type = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+    let charset = parts.find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"').to_string())
+    });
+    (mime_type, charset)
 }
 
 #[derive(Debug)]
 pub enum FetchError {
     HttpError(u16),
-    InvalidContentType(String),
+    /// Rejected before the body was downloaded; `skipped_bytes` is the body size when known
+    InvalidContentType {
+        content_type: String,
+        skipped_bytes: Option<u64>,
+    },
+    /// Not requested because the URL's pattern only ever served this rejected type
+    Skipped(String),
     TooLarge(u64),
+    Dns(DnsError),
+    /// Not requested because the host's circuit breaker is open
+    HostUnavailable(String),
     RequestError(reqwest::Error),
 }
 
+impl FetchError {
+    /// Bytes that were not downloaded because of this error, if known
+    pub fn bytes_saved(&self) -> u64 {
+        match self {
+            FetchError::InvalidContentType { skipped_bytes, .. } => skipped_bytes.unwrap_or(0),
+            _ => 0,
+        }
+    }
+}
+
 impl From<reqwest::Error> for FetchError {
     fn from(err: reqwest::Error) -> Self {
         FetchError::RequestError(err)
@@ -66,11 +404,88 @@ impl std::fmt::Display for FetchError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             FetchError::HttpError(code) => write!(f, "HTTP error: {}", code),
-            FetchError::InvalidContentType(ct) => write!(f, "Invalid content type: {}", ct),
+            FetchError::InvalidContentType { content_type, .. } => write!(f, "Invalid content type: {}", content_type),
+            FetchError::Skipped(ct) => write!(f, "Skipped, pattern serves: {}", ct),
             FetchError::TooLarge(size) => write!(f, "Response too large: {} bytes", size),
+            FetchError::Dns(e) => write!(f, "{}", e),
+            FetchError::HostUnavailable(host) => write!(f, "Host unreachable, cooling down: {}", host),
             FetchError::RequestError(e) => write!(f, "Request error: {}", e),
         }
     }
 }
 
 impl std::error::Error for FetchError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RequestProfile;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve `/start` as a redirect to the same server under its IP, which `localhost` profiles don't cover
+    async fn redirecting_server() -> (u16, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let served = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut head = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+                    match stream.read(&mut buf).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => head.extend_from_slice(&buf[..n]),
+                    }
+                }
+                let head = String::from_utf8_lossy(&head).to_ascii_lowercase();
+                let response = if head.starts_with("get /start ") {
+                    format!(
+                        "HTTP/1.1 302 Found\r\nLocation: http://127.0.0.1:{}/land\r\nSet-Cookie: first=1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        port
+                    )
+                } else {
+                    "HTTP/1.1 200 OK\r\nSet-Cookie: second=2\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok".to_string()
+                };
+                served.lock().unwrap().push(head);
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        (port, requests)
+    }
+
+    #[tokio::test]
+    async fn profile_stays_with_its_host_across_redirects() {
+        let (port, requests) = redirecting_server().await;
+        let config = CrawlerConfig {
+            private_allowlist: vec!["127.0.0.1".to_string(), "localhost".to_string()],
+            profiles: vec![RequestProfile {
+                host: "localhost".to_string(),
+                headers: HashMap::from([("X-Secret".to_string(), "hunter2".to_string())]),
+                cookie_jar: true,
+                persist_cookies: true,
+                cookies: vec!["session=abc".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let client = HttpClient::new(&config).unwrap();
+
+        let body = client.fetch_bytes(&format!("http://localhost:{}/start", port), 1024).await.unwrap();
+        assert_eq!(body, b"ok");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains("x-secret: hunter2") && requests[0].contains("cookie: session=abc"));
+        assert!(!requests[1].contains("x-secret") && !requests[1].contains("cookie:"));
+
+        // The landing host's cookie is not kept in the jar of the host that redirected
+        let jars = client.profiles().persistent_cookies();
+        assert_eq!(jars, [("localhost".to_string(), "first=1; session=abc".to_string())]);
+    }
+}
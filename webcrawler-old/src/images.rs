@@ -0,0 +1,315 @@
+use crate::http_client::HttpClient;
+use crate::normalize::{collapse_whitespace, decode_attribute, decode_entities};
+use crate::rate_limiter::RateLimiter;
+use lol_html::html_content::Element;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::Cursor;
+use url::Url;
+
+/// Where thumbnails are written when thumbnail mode is on
+pub const THUMBNAIL_DIR: &str = "output/thumbnails";
+/// Longest side of a thumbnail in pixels
+const THUMBNAIL_SIZE: u32 = 200;
+const MAX_THUMBNAIL_SOURCE_BYTES: usize = 10 * 1024 * 1024;
+const MAX_THUMBNAILS_PER_PAGE: usize = 10;
+
+const MAX_IMAGES: usize = 500;
+/// Characters of surrounding text kept on each side of an image
+const NEARBY_CHARS: usize = 200;
+/// Images declared smaller than this on a side are spacers, icons or tracking pixels
+const MIN_DIMENSION: u32 = 16;
+
+/// An image on a page, as the page describes it; the image itself is not fetched
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Image {
+    pub url: String,
+    pub srcset: Vec<ImageSource>,
+    pub alt: Option<String>,
+    pub title: Option<String>,
+    /// From the width and height attributes, or from the image when a thumbnail was made
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Caption of the enclosing `<figure>`
+    pub caption: Option<String>,
+    /// Text just before and after the image
+    pub nearby_text: String,
+    /// Path of the JPEG thumbnail, in thumbnail mode only
+    pub thumbnail: Option<String>,
+}
+
+/// A `srcset` candidate with its width (`800w`) or pixel density (`2x`) descriptor
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ImageSource {
+    pub url: String,
+    pub descriptor: Option<String>,
+}
+
+/// An image waiting for the text around it
+struct PendingImage {
+    image: Image,
+    figure: Option<usize>,
+    /// Raw text that preceded the image
+    before: String,
+    /// Raw text that followed it, until there is enough
+    after: String,
+}
+
+/// Collects images from lol_html events, with their figure captions and surrounding text
+/// The `figure img` handler must be registered before the `img` one, so the image knows it is in a figure
+pub struct ImageCollector {
+    base_url: Url,
+    images: Vec<PendingImage>,
+    /// Raw visible text, trimmed to the last few hundred characters
+    recent_text: String,
+    captions: Vec<String>,
+    in_figure: bool,
+}
+
+impl ImageCollector {
+    pub fn new(base_url: Url) -> Self {
+        Self {
+            base_url,
+            images: Vec::new(),
+            recent_text: String::new(),
+            captions: Vec::new(),
+            in_figure: false,
+        }
+    }
+
+    /// Resolve image URLs against a `<base href>` from here on
+    pub fn set_base_url(&mut self, base_url: Url) {
+        self.base_url = base_url;
+    }
+
+    pub fn open_figure(&mut self) {
+        self.captions.push(String::new());
+    }
+
+    pub fn push_caption(&mut self, chunk: &str) {
+        if let Some(caption) = self.captions.last_mut() {
+            caption.push_str(chunk);
+        }
+    }
+
+    pub fn mark_in_figure(&mut self) {
+        self.in_figure = true;
+    }
+
+    /// Record an `<img>`; lazy-loaded images give their URLs in `data-src` and `data-srcset`,
+    /// which win over `src` and `srcset` as those then only hold a placeholder such as a `data:` URL
+    pub fn add_image(&mut self, el: &Element) {
+        let in_figure = std::mem::take(&mut self.in_figure);
+        if self.images.len() >= MAX_IMAGES {
+            return;
+        }
+
+        let attribute = |name: &str| el.get_attribute(name).map(|value| decode_attribute(&value));
+        let text_attribute = |name: &str| {
+            el.get_attribute(name)
+                .map(|value| collapse_whitespace(&decode_attribute(&value)))
+                .filter(|value| !value.is_empty())
+        };
+        let dimension = |name: &str| el.get_attribute(name).and_then(|value| value.trim().trim_end_matches("px").parse::<u32>().ok());
+
+        let (width, height) = (dimension("width"), dimension("height"));
+        if width.is_some_and(|width| width < MIN_DIMENSION) || height.is_some_and(|height| height < MIN_DIMENSION) {
+            return;
+        }
+
+        let srcset: Vec<ImageSource> = ["data-srcset", "srcset"]
+            .into_iter()
+            .filter_map(attribute)
+            .map(|srcset| self.parse_srcset(&srcset))
+            .find(|sources| !sources.is_empty())
+            .unwrap_or_default();
+        let src = ["data-src", "src"].into_iter().filter_map(attribute).find_map(|src| self.resolve(&src));
+        let Some(url) = src.or_else(|| srcset.first().map(|source| source.url.clone())) else {
+            return;
+        };
+
+        let before_start = floor_char_boundary(&self.recent_text, self.recent_text.len().saturating_sub(NEARBY_CHARS * 4));
+        self.images.push(PendingImage {
+            image: Image {
+                url,
+                srcset,
+                alt: text_attribute("alt"),
+                title: text_attribute("title"),
+                width,
+                height,
+                ..Default::default()
+            },
+            figure: in_figure.then(|| self.captions.len().checked_sub(1)).flatten(),
+            before: self.recent_text[before_start..].to_string(),
+            after: String::new(),
+        });
+    }
+
+    /// Add a raw chunk of visible text
+    pub fn push_text(&mut self, chunk: &str) {
+        for pending in self.images.iter_mut().rev() {
+            if pending.after.len() >= NEARBY_CHARS * 4 {
+                break;
+            }
+            pending.after.push_str(chunk);
+        }
+
+        self.recent_text.push_str(chunk);
+        if self.recent_text.len() > NEARBY_CHARS * 8 {
+            let start = floor_char_boundary(&self.recent_text, self.recent_text.len() - NEARBY_CHARS * 4);
+            self.recent_text.drain(..start);
+        }
+    }
+
+    pub fn finish(self) -> Vec<Image> {
+        let captions: Vec<String> = self
+            .captions
+            .iter()
+            .map(|caption| collapse_whitespace(&decode_entities(caption)))
+            .collect();
+
+        self.images
+            .into_iter()
+            .map(|pending| {
+                let mut image = pending.image;
+                image.caption = pending
+                    .figure
+                    .and_then(|figure| captions.get(figure))
+                    .filter(|caption| !caption.is_empty())
+                    .cloned();
+                let before = collapse_whitespace(&decode_entities(&pending.before));
+                let after = collapse_whitespace(&decode_entities(&pending.after));
+                image.nearby_text = format!("{} {}", tail_words(&before, NEARBY_CHARS), head_words(&after, NEARBY_CHARS))
+                    .trim()
+                    .to_string();
+                image
+            })
+            .collect()
+    }
+
+    fn resolve(&self, src: &str) -> Option<String> {
+        let url = self.base_url.join(src.trim()).ok()?;
+        (url.scheme() == "http" || url.scheme() == "https").then(|| url.to_string())
+    }
+
+    /// Parse `a.jpg 1x, b.jpg 2x` into candidates; URLs may contain commas as long as they don't end in one
+    fn parse_srcset(&self, srcset: &str) -> Vec<ImageSource> {
+        let mut sources = Vec::new();
+        let mut tokens = srcset.split_ascii_whitespace();
+        while let Some(token) = tokens.next() {
+            let (url, descriptor) = match token.strip_suffix(',') {
+                Some(url) => (url, None),
+                None => (token, tokens.next().map(|descriptor| descriptor.trim_end_matches(',').to_string())),
+            };
+            if let Some(url) = self.resolve(url.trim_start_matches(',')) {
+                sources.push(ImageSource { url, descriptor });
+            }
+        }
+        sources
+    }
+}
+
+/// Fetch the page's first images and save a small JPEG thumbnail of each, filling in their real size
+/// Images that fail to download or decode are left without a thumbnail
+pub async fn make_thumbnails(images: &mut [Image], http_client: &HttpClient, rate_limiter: &RateLimiter) {
+    for image in images.iter_mut().take(MAX_THUMBNAILS_PER_PAGE) {
+        rate_limiter.wait_if_needed(&image.url).await;
+        let Ok(bytes) = http_client.fetch_bytes(&image.url, MAX_THUMBNAIL_SOURCE_BYTES).await else {
+            continue;
+        };
+
+        let path = thumbnail_path(&image.url);
+        let saved = tokio::task::spawn_blocking({
+            let path = path.clone();
+            move || save_thumbnail(&bytes, &path)
+        })
+        .await;
+
+        if let Ok(Some((width, height))) = saved {
+            image.width = Some(width);
+            image.height = Some(height);
+            image.thumbnail = Some(path);
+        }
+    }
+}
+
+/// Decode an image, save its thumbnail as JPEG, and return the original dimensions
+fn save_thumbnail(bytes: &[u8], path: &str) -> Option<(u32, u32)> {
+    let decoded = image::ImageReader::new(Cursor::new(bytes)).with_guessed_format().ok()?.decode().ok()?;
+    let thumbnail = decoded.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgb8();
+    thumbnail.save_with_format(path, image::ImageFormat::Jpeg).ok()?;
+    Some((decoded.width(), decoded.height()))
+}
+
+fn thumbnail_path(url: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    format!("{}/{:016x}.jpg", THUMBNAIL_DIR, hasher.finish())
+}
+
+/// The last whole words of a text within `max` characters
+fn tail_words(text: &str, max: usize) -> &str {
+    let count = text.chars().count();
+    if count <= max {
+        return text;
+    }
+    let start = text.char_indices().nth(count - max).map(|(index, _)| index).unwrap_or(0);
+    let tail = &text[start..];
+    // Drop the word that was cut, unless the cut fell on a space
+    if text[..start].ends_with(' ') {
+        tail
+    } else {
+        tail.split_once(' ').map(|(_, rest)| rest).unwrap_or("")
+    }
+}
+
+/// The first whole words of a text within `max` characters
+fn head_words(text: &str, max: usize) -> &str {
+    let Some((end, _)) = text.char_indices().nth(max) else {
+        return text;
+    };
+    let head = &text[..end];
+    if text[end..].starts_with(' ') {
+        head
+    } else {
+        head.rsplit_once(' ').map(|(rest, _)| rest).unwrap_or("")
+    }
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extractors::Extractors;
+    use crate::parser::parse_html;
+
+    #[test]
+    fn lazy_loaded_urls_win_over_placeholders() {
+        let html = r#"
+            <img src="data:image/gif;base64,R0lGODlhAQABAAAAACw=" data-src="/media/real.jpg" alt="Lazy">
+            <img src="/media/plain.jpg" data-srcset="data:image/gif;base64,R0lGODlhAQABAAAAACw= 1x" alt="Plain">
+            <img src="data:image/gif;base64,R0lGODlhAQABAAAAACw=" srcset="/media/small.jpg 1x, /media/large.jpg 2x" alt="Srcset">
+            <img src="data:image/gif;base64,R0lGODlhAQABAAAAACw=" alt="Placeholder only">"#;
+        let parsed = parse_html(html.to_string(), "https://example.org/post", &Extractors::new()).unwrap();
+        let images: Vec<(&str, Option<&str>)> = parsed
+            .images
+            .iter()
+            .map(|image| (image.url.as_str(), image.alt.as_deref()))
+            .collect();
+        assert_eq!(
+            images,
+            [
+                ("https://example.org/media/real.jpg", Some("Lazy")),
+                ("https://example.org/media/plain.jpg", Some("Plain")),
+                ("https://example.org/media/small.jpg", Some("Srcset")),
+            ]
+        );
+        assert_eq!(parsed.images[2].srcset.len(), 2);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::parser::ParsedHtml;
+use crate::url_store::DEFAULT_PRIORITY;
+use serde::{Deserialize, Serialize};
+
+/// Frontier priority for editions of a page in a preferred language
+pub const PREFERRED_LANGUAGE_PRIORITY: u8 = 150;
+
+/// Leading characters of the text given to the detector, plenty for a stable answer
+const SAMPLE_CHARS: usize = 4000;
+/// Shorter texts don't carry enough trigrams to tell related languages apart
+const MIN_SAMPLE_CHARS: usize = 40;
+
+/// ISO 639-1 codes of the detector's languages, by their ISO 639-3 code
+const ISO_639_1: [(&str, &str); 70] = [
+    ("afr", "af"), ("aka", "ak"), ("amh", "am"), ("ara", "ar"), ("aze", "az"), ("bel", "be"),
+    ("ben", "bn"), ("bul", "bg"), ("cat", "ca"), ("ces", "cs"), ("cmn", "zh"), ("cym", "cy"),
+    ("dan", "da"), ("deu", "de"), ("ell", "el"), ("eng", "en"), ("epo", "eo"), ("est", "et"),
+    ("fin", "fi"), ("fra", "fr"), ("guj", "gu"), ("heb", "he"), ("hin", "hi"), ("hrv", "hr"),
+    ("hun", "hu"), ("hye", "hy"), ("ind", "id"), ("ita", "it"), ("jav", "jv"), ("jpn", "ja"),
+    ("kan", "kn"), ("kat", "ka"), ("khm", "km"), ("kor", "ko"), ("lat", "la"), ("lav", "lv"),
+    ("lit", "lt"), ("mal", "ml"), ("mar", "mr"), ("mkd", "mk"), ("mya", "my"), ("nep", "ne"),
+    ("nld", "nl"), ("nob", "nb"), ("ori", "or"), ("pan", "pa"), ("pes", "fa"), ("pol", "pl"),
+    ("por", "pt"), ("ron", "ro"), ("rus", "ru"), ("sin", "si"), ("slk", "sk"), ("slv", "sl"),
+    ("sna", "sn"), ("spa", "es"), ("srp", "sr"), ("swe", "sv"), ("tam", "ta"), ("tel", "te"),
+    ("tgl", "tl"), ("tha", "th"), ("tuk", "tk"), ("tur", "tr"), ("ukr", "uk"), ("urd", "ur"),
+    ("uzb", "uz"), ("vie", "vi"), ("yid", "yi"), ("zul", "zu"),
+];
+
+/// Language of the page text according to the built-in trigram detector
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DetectedLanguage {
+    /// ISO 639-1 code, comparable with `lang` attributes
+    pub code: String,
+    /// From 0 to 1
+    pub confidence: f64,
+    /// Whether the detector is confident enough to act on the result
+    pub reliable: bool,
+}
+
+/// An edition of the page in another language, from `<link rel="alternate" hreflang>`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LanguageAlternate {
+    /// Language tag as given, e.g. `de`, `pt-BR`, or `x-default` for the fallback edition
+    pub hreflang: String,
+    pub url: String,
+}
+
+/// Detect the language of a text, None when it is too short or the detector has no answer
+pub fn detect(text: &str) -> Option<DetectedLanguage> {
+    let sample_end = text.char_indices().nth(SAMPLE_CHARS).map(|(index, _)| index).unwrap_or(text.len());
+    let sample = &text[..sample_end];
+    if sample.chars().filter(|c| c.is_alphabetic()).count() < MIN_SAMPLE_CHARS {
+        return None;
+    }
+
+    let info = whatlang::detect(sample)?;
+    let code = info.lang().code();
+    let code = ISO_639_1
+        .iter()
+        .find(|(iso_639_3, _)| *iso_639_3 == code)
+        .map(|(_, iso_639_1)| *iso_639_1)
+        .unwrap_or(code);
+    Some(DetectedLanguage {
+        code: code.to_string(),
+        confidence: info.confidence(),
+        reliable: info.is_reliable(),
+    })
+}
+
+/// Detect the language of a record's main text, and use it when the page declares none
+pub fn identify(parsed: &mut ParsedHtml) {
+    let text = if parsed.main_text.len() >= MIN_SAMPLE_CHARS { &parsed.main_text } else { &parsed.full_text };
+    parsed.detected_language = detect(text);
+
+    if parsed.language.as_deref().is_none_or(|language| language.trim().is_empty())
+        && let Some(detected) = parsed.detected_language.as_ref().filter(|detected| detected.reliable) {
+        parsed.language = Some(detected.code.clone());
+    }
+}
+
+/// Primary subtag of a language tag, lowercased: `pt` for `pt-BR`
+pub fn primary_subtag(tag: &str) -> String {
+    tag.trim().split(['-', '_']).next().unwrap_or_default().to_ascii_lowercase()
+}
+
+/// Frontier priority for a link by the language edition it leads to, None to leave it out
+/// Links that aren't a language edition of the page keep the default priority
+pub fn link_priority(url: &str, alternates: &[LanguageAlternate], preferred: &[String], excluded: &[String]) -> Option<u8> {
+    let Some(alternate) = alternates.iter().find(|alternate| alternate.url == url) else {
+        return Some(DEFAULT_PRIORITY);
+    };
+    let language = primary_subtag(&alternate.hreflang);
+    let listed = |languages: &[String]| languages.iter().any(|listed| primary_subtag(listed) == language);
+
+    if listed(excluded) {
+        None
+    } else if listed(preferred) {
+        Some(PREFERRED_LANGUAGE_PRIORITY)
+    } else {
+        Some(DEFAULT_PRIORITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENGLISH: &str = "The quick brown fox jumps over the lazy dog while the farmer watches from the porch \
+        and wonders whether the weather will hold until the harvest is finished next week.";
+    const GERMAN: &str = "Der schnelle braune Fuchs springt über den faulen Hund, während der Bauer von der Veranda \
+        aus zusieht und sich fragt, ob das Wetter bis zum Ende der Ernte in der nächsten Woche hält.";
+
+    fn alternate(hreflang: &str, url: &str) -> LanguageAlternate {
+        LanguageAlternate {
+            hreflang: hreflang.to_string(),
+            url: url.to_string(),
+        }
+    }
+
+    #[test]
+    fn detects_languages_as_iso_639_1() {
+        let english = detect(ENGLISH).unwrap();
+        assert_eq!(english.code, "en");
+        assert!(english.reliable);
+        assert_eq!(detect(GERMAN).unwrap().code, "de");
+        assert_eq!(detect("Too short to tell"), None);
+        assert_eq!(detect("1234 5678 !!! ... 9999 0000 ---- 1111 2222 3333 4444 5555 6666"), None);
+    }
+
+    #[test]
+    fn identify_fills_in_a_missing_declared_language() {
+        let mut parsed = ParsedHtml {
+            main_text: GERMAN.to_string(),
+            ..Default::default()
+        };
+        identify(&mut parsed);
+        assert_eq!(parsed.language.as_deref(), Some("de"));
+
+        let mut declared = ParsedHtml {
+            language: Some("en-GB".to_string()),
+            main_text: GERMAN.to_string(),
+            ..Default::default()
+        };
+        identify(&mut declared);
+        assert_eq!(declared.language.as_deref(), Some("en-GB"));
+        assert_eq!(declared.detected_language.map(|detected| detected.code).as_deref(), Some("de"));
+    }
+
+    #[test]
+    fn identify_falls_back_to_the_full_text() {
+        let mut parsed = ParsedHtml {
+            main_text: "Short".to_string(),
+            full_text: ENGLISH.to_string(),
+            ..Default::default()
+        };
+        identify(&mut parsed);
+        assert_eq!(parsed.language.as_deref(), Some("en"));
+    }
+
+    #[test]
+    fn link_priority_follows_language_preferences() {
+        let alternates = [
+            alternate("de-AT", "https://de.wikipedia.org/wiki/A"),
+            alternate("pt_BR", "https://pt.wikipedia.org/wiki/A"),
+            alternate("x-default", "https://www.wikipedia.org/"),
+        ];
+        let preferred = ["DE".to_string()];
+        let excluded = ["pt".to_string()];
+        let priority = |url: &str| link_priority(url, &alternates, &preferred, &excluded);
+
+        assert_eq!(priority("https://de.wikipedia.org/wiki/A"), Some(PREFERRED_LANGUAGE_PRIORITY));
+        assert_eq!(priority("https://pt.wikipedia.org/wiki/A"), None);
+        assert_eq!(priority("https://www.wikipedia.org/"), Some(DEFAULT_PRIORITY));
+        assert_eq!(priority("https://en.wikipedia.org/wiki/B"), Some(DEFAULT_PRIORITY));
+        assert_eq!(primary_subtag(" zh-Hant-TW "), "zh");
+    }
+}
@@ -1,8 +1,29 @@
+mod feeds;
+mod circuit_breaker;
+mod config;
+mod content;
+mod dedup;
+mod dns;
+mod extractors;
+mod normalize;
 mod parser;
+mod probe;
+mod profiles;
+mod proxy;
 mod url_store;
 mod writer;
 mod http_client;
+mod images;
+mod language;
+mod metadata;
 mod rate_limiter;
+mod readability;
+mod robots;
+mod segments;
+mod sitemap;
+mod sniff;
+mod ssrf;
+mod structured_data;
 mod ui;
 
 use std::sync::Arc;
@@ -13,32 +34,51 @@ use futures::StreamExt;
 use tokio_stream::wrappers::ReceiverStream;
 use url_store::UrlStore;
 use writer::BufferedWriter;
-use http_client::HttpClient;
+use feeds::FeedPoller;
+use http_client::{FetchError, HttpClient};
+use config::CrawlerConfig;
+use content::{ContentError, ContentHandlers};
 use rate_limiter::RateLimiter;
+use sitemap::SitemapIngestor;
+use tokio::signal;
 use tokio::sync::mpsc;
 
 const MAX_PAGES: usize = 1_000_000;
 const CONCURRENCY: usize = 1_000;
 const CHANNEL_BUFFER: usize = 10_000;
+const CONFIG_PATH: &str = "crawler.json";
+const PARK_RELEASE_INTERVAL_SECS: u64 = 30;
+const COOKIE_SAVE_INTERVAL_SECS: u64 = 60;
 
 #[tokio::main]
 async fn main() {
     // Create output directory if it doesn't exist
     fs::create_dir_all("output").expect("Failed to create output directory");
     
+    let config = Arc::new(CrawlerConfig::load(CONFIG_PATH).expect("Failed to load config"));
+    
     let seeds = vec![
         "https://en.wikipedia.org/wiki/Full-text_search".to_string()
     ];
     
-    let (writer, writer_tx) = BufferedWriter::new("output/crawled_pages.jsonl")
+    if config.image_thumbnails {
+        fs::create_dir_all(images::THUMBNAIL_DIR).expect("Failed to create thumbnail directory");
+    }
+    
+    let (writer, writer_tx) = BufferedWriter::new("output/crawled_pages.jsonl", "output/images.jsonl", &config)
         .expect("Failed to create buffered writer");
-    tokio::spawn(writer.run());
+    let writer_task = tokio::spawn(writer.run());
     
-    let http_client = Arc::new(HttpClient::new().expect("Failed to create HTTP client"));
+    let http_client = Arc::new(HttpClient::new(&config).expect("Failed to create HTTP client"));
+    let content_handlers = Arc::new(ContentHandlers::with_defaults());
     let rate_limiter = RateLimiter::new();
     let url_store = UrlStore::new("output/visited_urls.db")
         .expect("Failed to open URL store");
     
+    for (host, cookies) in url_store.cookies() {
+        http_client.profiles().restore_cookies(&host, &cookies);
+    }
+    
     // Load existing page count from database
     let existing_pages = url_store.get_pages_crawled();
     let pages_count = Arc::new(AtomicUsize::new(existing_pages));
@@ -67,11 +107,41 @@ async fn main() {
         return;
     }
     
-    let stats = Arc::new(ui::CrawlerStats::new(
+    let mut stats = ui::CrawlerStats::new(
         pages_count.clone(),
         pages_written.clone(),
         queue_size.clone(),
-    ));
+    );
+    stats.proxies = http_client.proxy_pool();
+    let stats = Arc::new(stats);
+    
+    if let Some(proxies) = http_client.proxy_pool() {
+        tokio::spawn(proxies.run_health_checks());
+    }
+    
+    let sitemaps = SitemapIngestor::new(
+        http_client.clone(),
+        rate_limiter.clone(),
+        url_store.clone(),
+        stats.sitemap_urls.clone(),
+    );
+    
+    let feed_poller = FeedPoller::new(
+        http_client.clone(),
+        rate_limiter.clone(),
+        url_store.clone(),
+        stats.feed_urls.clone(),
+    );
+    tokio::spawn(feed_poller.run());
+    
+    // Ctrl-C outside the UI and termination signals stop the crawl like q does
+    tokio::spawn({
+        let stats = stats.clone();
+        async move {
+            shutdown_signal().await;
+            stats.stop();
+        }
+    });
     
     let ui_task = tokio::spawn({
         let stats = stats.clone();
@@ -82,35 +152,57 @@ async fn main() {
         }
     });
     
-    let (discovered_tx, mut discovered_rx) = mpsc::channel::<String>(CHANNEL_BUFFER);
-    let (processing_tx, processing_rx) = mpsc::channel::<String>(CHANNEL_BUFFER);
+    let (discovered_tx, mut discovered_rx) = mpsc::channel::<(String, u8)>(CHANNEL_BUFFER);
+    let (processing_tx, processing_rx) = mpsc::channel::<(String, u8)>(CHANNEL_BUFFER);
     
     let discovered_tx = Arc::new(discovered_tx);
-    let processing_tx = Arc::new(processing_tx);
     
     // Task to add discovered URLs to frontier (workers will pull as needed)
     let frontier_task = tokio::spawn({
         let url_store = url_store.clone();
         async move {
-            while let Some(link) = discovered_rx.recv().await {
+            while let Some((link, priority)) = discovered_rx.recv().await {
                 // Just persist to DB, don't send to processing channel
-                url_store.add_to_frontier(&link);
+                url_store.add_to_frontier_with_priority(&link, priority);
+            }
+        }
+    });
+    
+    // Return URLs of unreachable hosts to the frontier once their cool-down ends
+    tokio::spawn({
+        let url_store = url_store.clone();
+        async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(PARK_RELEASE_INTERVAL_SECS)).await;
+                url_store.release_parked_hosts();
+            }
+        }
+    });
+    
+    // Persist cookie jars of request profiles that keep them across runs
+    tokio::spawn({
+        let url_store = url_store.clone();
+        let http_client = http_client.clone();
+        async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(COOKIE_SAVE_INTERVAL_SECS)).await;
+                save_cookies(&http_client, &url_store);
             }
         }
     });
     
     // Seed the processing queue with URLs from frontier
+    // Once the crawl stops, the loop ends and drops the sender, which ends the workers' stream
     let url_store_clone = url_store.clone();
-    let processing_tx_clone = processing_tx.clone();
     let queue_size_clone = queue_size.clone();
     let stats_clone = stats.clone();
     tokio::spawn(async move {
-        loop {
+        while !stats_clone.should_stop() {
             // Keep queue fed with URLs from frontier
             let current_queue = queue_size_clone.load(Ordering::Relaxed);
-            if current_queue < CHANNEL_BUFFER / 2 && !stats_clone.should_stop() {
-                if let Some(url) = url_store_clone.pop_from_frontier() {
-                    match processing_tx_clone.try_send(url) {
+            if current_queue < CHANNEL_BUFFER / 2 {
+                if let Some(entry) = url_store_clone.pop_from_frontier() {
+                    match processing_tx.try_send(entry) {
                         Ok(_) => {
                             queue_size_clone.fetch_add(1, Ordering::Relaxed);
                         }
@@ -130,7 +222,7 @@ async fn main() {
     });
     
     ReceiverStream::new(processing_rx)
-        .for_each_concurrent(CONCURRENCY, |url| {
+        .for_each_concurrent(CONCURRENCY, |(url, priority)| {
             let url_store = url_store.clone();
             let pages_count = pages_count.clone();
             let pages_written = pages_written.clone();
@@ -138,8 +230,11 @@ async fn main() {
             let discovered_tx = discovered_tx.clone();
             let writer_tx = writer_tx.clone();
             let http_client = http_client.clone();
+            let content_handlers = content_handlers.clone();
             let rate_limiter = rate_limiter.clone();
             let stats = stats.clone();
+            let sitemaps = sitemaps.clone();
+            let config = config.clone();
             
             async move {
                 queue_size.fetch_sub(1, Ordering::Relaxed);
@@ -147,16 +242,24 @@ async fn main() {
                 
                 let current_count = pages_count.fetch_add(1, Ordering::Relaxed) + 1;
                 if current_count > MAX_PAGES || stats.should_stop() {
+                    stats.stop();
                     stats.active_workers.fetch_sub(1, Ordering::Relaxed);
                     return;
                 }
                 
-                match process_link(url.clone(), http_client, rate_limiter, writer_tx).await {
-                    Ok((parsed, child_links)) => {
-                        pages_written.fetch_add(1, Ordering::Relaxed);
+                match process_link(url.clone(), http_client.clone(), content_handlers, rate_limiter, writer_tx, url_store.clone(), config).await {
+                    Ok((parsed, child_links, written)) => {
+                        if written {
+                            pages_written.fetch_add(1, Ordering::Relaxed);
+                        }
+                        if parsed.duplicate_of.is_some() {
+                            stats.duplicates.fetch_add(1, Ordering::Relaxed);
+                        }
+                        stats.invalid_json_ld.fetch_add(parsed.invalid_json_ld, Ordering::Relaxed);
                         
                         // Track domain
                         stats.increment_domain(&url);
+                        sitemaps.discover(&url);
                         
                         // Persist page count every 10 pages
                         let current = pages_count.load(Ordering::Relaxed);
@@ -165,15 +268,29 @@ async fn main() {
                         }
                         
                         if let Some(canonical) = &parsed.canonical_url
-                            && canonical != &parsed.url {
+                            && *canonical != normalize::normalize_url(&parsed.url) {
                             url_store.mark_visited(canonical);
                         }
                         
+                        // Only a host's own feeds are polled, as with sitemaps
+                        for feed in parsed.feeds.iter().filter(|feed| feeds::same_host(feed, &url)) {
+                            url_store.add_feed(feed, feeds::INITIAL_FEED_INTERVAL_SECS);
+                        }
+                        
                         for link in child_links {
                             let _ = discovered_tx.send(link).await;
                         }
                     }
                     Err(e) => {
+                        if let Some(fetch_error) = e.downcast_ref::<FetchError>() {
+                            stats.bytes_saved.fetch_add(fetch_error.bytes_saved() as usize, Ordering::Relaxed);
+                        }
+                        if let Some(ContentError::Parse(_)) = e.downcast_ref::<ContentError>() {
+                            stats.parse_errors.fetch_add(1, Ordering::Relaxed);
+                        }
+                        if let Some(cool_down) = http_client.host_down_for(&url) {
+                            url_store.park(&url, priority, cool_down.as_secs());
+                        }
                         stats.add_error(format!("{}: {}", url, e));
                     }
                 }
@@ -183,9 +300,15 @@ async fn main() {
         })
         .await;
     
+    // Closing the channel lets the writer finish its open segments
+    drop(writer_tx);
+    writer_task.await.unwrap();
+    
     // Save final page count
     url_store.set_pages_crawled(pages_count.load(Ordering::Relaxed));
+    save_cookies(&http_client, &url_store);
     
+    drop(discovered_tx);
     frontier_task.await.unwrap();
     ui_task.await.unwrap();
 }
@@ -193,15 +316,103 @@ async fn main() {
 async fn process_link(
     link: String,
     http_client: Arc<HttpClient>,
+    content_handlers: Arc<ContentHandlers>,
     rate_limiter: RateLimiter,
     writer_tx: mpsc::Sender<parser::ParsedHtml>,
-) -> Result<(parser::ParsedHtml, Vec<String>), Box<dyn std::error::Error>> {
+    url_store: UrlStore,
+    config: Arc<CrawlerConfig>,
+) -> Result<(parser::ParsedHtml, Vec<(String, u8)>, bool), Box<dyn std::error::Error>> {
     rate_limiter.wait_if_needed(&link).await;
-    let html_content = http_client.fetch(&link).await?;
-    let parsed = parser::parse_html(html_content, &link);
-    let links: Vec<String> = parsed.links.clone();
+    let content = http_client.fetch(&link, |mime_type| content_handlers.max_size(mime_type)).await?;
+    let dns_time = content.dns_time;
+    let header_robots = content.robots.clone();
+    let mut parsed = content_handlers.extract(content, &link).await?;
+    parsed.dns_ms = dns_time.map(|elapsed| elapsed.as_millis() as u64);
+    normalize::normalize_record(&mut parsed, config.unicode_normalization);
+    parsed.content_hash = dedup::content_hash(&parsed.main_text);
+    parsed.robots.extend(header_robots);
+    parsed.robots.sort();
+    parsed.robots.dedup();
+    
+    let page_nofollow = config.respect_nofollow && robots::has_directive(&parsed.robots, robots::NOFOLLOW);
+    let mut links: Vec<String> = if page_nofollow {
+        Vec::new()
+    } else {
+        parsed
+            .links
+            .iter()
+            .filter(|link| !config.respect_nofollow || !link.rel.is_nofollow())
+            .map(|link| link.url.clone())
+            .collect()
+    };
+    
+    // Language editions from hreflang alternates are followed like body links
+    if !page_nofollow {
+        for alternate in &parsed.alternates {
+            if parser::is_crawlable(&alternate.url) && !links.contains(&alternate.url) {
+                links.push(alternate.url.clone());
+            }
+        }
+    }
+    
+    // A meta refresh redirect is followed like an HTTP one, whatever the page's nofollow
+    if let Some(target) = &parsed.redirect
+        && !links.contains(target) {
+        links.push(target.clone());
+    }
+    
+    // Language editions are queued by the crawl's language preferences
+    let links: Vec<(String, u8)> = links
+        .into_iter()
+        .filter_map(|link| {
+            let priority = language::link_priority(&link, &parsed.alternates, &config.preferred_languages, &config.excluded_languages)?;
+            Some((link, priority))
+        })
+        .collect();
     
-    writer_tx.send(parsed.clone()).await?;
+    let indexable = parsed.redirect.is_none()
+        && (!config.respect_noindex || !robots::has_directive(&parsed.robots, robots::NOINDEX));
     
-    Ok((parsed, links))
+    // Exact duplicates become aliases of the first page; its outlinks were already queued from there
+    if indexable && let Some(content_hash) = &parsed.content_hash {
+        parsed.duplicate_of = url_store.record_content_hash(content_hash, &link);
+        if parsed.duplicate_of.is_some() {
+            return Ok((parsed, Vec::new(), false));
+        }
+    }
+    
+    if indexable {
+        // Near-duplicates are written, grouped into clusters the index can collapse
+        if let Some(fingerprint) = dedup::simhash(&parsed.main_text) {
+            parsed.simhash = Some(format!("{:016x}", fingerprint));
+            parsed.cluster = Some(url_store.simhash_cluster(fingerprint, &link));
+        }
+        if config.image_thumbnails {
+            images::make_thumbnails(&mut parsed.images, &http_client, &rate_limiter).await;
+        }
+        writer_tx.send(parsed.clone()).await?;
+    }
+    
+    Ok((parsed, links, indexable))
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM where there is one
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM");
+        tokio::select! {
+            _ = signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    signal::ctrl_c().await.ok();
+}
+
+fn save_cookies(http_client: &HttpClient, url_store: &UrlStore) {
+    for (host, cookies) in http_client.profiles().persistent_cookies() {
+        url_store.put_cookies(&host, &cookies);
+    }
 }
@@ -0,0 +1,269 @@
+use crate::parser::MetaTag;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Candidates for each resolved field, tried in order; the first usable value wins
+const TITLE_NAMES: [&str; 4] = ["og:title", "twitter:title", "dc.title", "dcterms.title"];
+const DESCRIPTION_NAMES: [&str; 5] = [
+    "description",
+    "og:description",
+    "twitter:description",
+    "dc.description",
+    "dcterms.description",
+];
+const IMAGE_NAMES: [&str; 5] = ["og:image", "og:image:url", "og:image:secure_url", "twitter:image", "twitter:image:src"];
+const AUTHOR_NAMES: [&str; 5] = ["author", "article:author", "dc.creator", "dcterms.creator", "twitter:creator"];
+const KEYWORD_NAMES: [&str; 4] = ["keywords", "article:tag", "dc.subject", "dcterms.subject"];
+const PUBLISHED_NAMES: [&str; 6] = [
+    "article:published_time",
+    "dcterms.created",
+    "dcterms.issued",
+    "dc.date",
+    "dcterms.date",
+    "date",
+];
+const MODIFIED_NAMES: [&str; 4] = ["article:modified_time", "og:updated_time", "dcterms.modified", "last-modified"];
+
+/// Metadata from the page's `<meta>` tags, by vocabulary and resolved across vocabularies
+///
+/// Resolved fields take the first usable value in this order:
+/// - title: `og:title`, `twitter:title`, `dc.title`, `dcterms.title`, then the `<title>` element
+/// - description: `description`, `og:description`, `twitter:description`, `dc.description`, `dcterms.description`
+/// - image: `og:image`, `og:image:url`, `og:image:secure_url`, `twitter:image`, `twitter:image:src`
+/// - author: `author`, `article:author`, `dc.creator`, `dcterms.creator`, `twitter:creator`
+/// - keywords: `keywords`, else every `article:tag`, else `dc.subject`, else `dcterms.subject`
+/// - published: `article:published_time`, `dcterms.created`, `dcterms.issued`, `dc.date`, `dcterms.date`, `date`
+/// - modified: `article:modified_time`, `og:updated_time`, `dcterms.modified`, `last-modified`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PageMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Absolute URL of the preview image
+    pub image: Option<String>,
+    pub author: Option<String>,
+    pub keywords: Vec<String>,
+    /// Dates in RFC 3339; values that don't parse as a date are skipped
+    pub published: Option<String>,
+    pub modified: Option<String>,
+    pub open_graph: OpenGraph,
+    pub twitter: TwitterCard,
+    pub dublin_core: DublinCore,
+}
+
+/// OpenGraph `og:*` properties
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct OpenGraph {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    /// `og:type`, e.g. `article` or `website`
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub site_name: Option<String>,
+    pub url: Option<String>,
+}
+
+/// Twitter Card `twitter:*` fields
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TwitterCard {
+    /// Card type, e.g. `summary_large_image`
+    pub card: Option<String>,
+    pub site: Option<String>,
+    pub creator: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+}
+
+/// Dublin Core `dc.*` and `dcterms.*` elements, `dc.` taking precedence
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DublinCore {
+    pub title: Option<String>,
+    pub creator: Option<String>,
+    pub subject: Option<String>,
+    pub description: Option<String>,
+    pub publisher: Option<String>,
+    pub date: Option<String>,
+    pub language: Option<String>,
+    pub rights: Option<String>,
+}
+
+/// Resolve the page metadata from its meta tags and `<title>`
+/// Image and URL values are made absolute against the page URL
+pub fn page_metadata(meta_tags: &[MetaTag], title: Option<&str>, base_url: &Url) -> PageMetadata {
+    let get = |name: &str| first(meta_tags, &[name]);
+    let url = |name: &str| get(name).and_then(|value| absolute_url(&value, base_url));
+    let dc = |element: &str| first(meta_tags, &[format!("dc.{}", element).as_str(), format!("dcterms.{}", element).as_str()]);
+
+    let open_graph = OpenGraph {
+        title: get("og:title"),
+        description: get("og:description"),
+        image: url("og:image"),
+        kind: get("og:type"),
+        site_name: get("og:site_name"),
+        url: url("og:url"),
+    };
+    let twitter = TwitterCard {
+        card: get("twitter:card"),
+        site: get("twitter:site"),
+        creator: get("twitter:creator"),
+        title: get("twitter:title"),
+        description: get("twitter:description"),
+        image: url("twitter:image"),
+    };
+    let dublin_core = DublinCore {
+        title: dc("title"),
+        creator: dc("creator"),
+        subject: dc("subject"),
+        description: dc("description"),
+        publisher: dc("publisher"),
+        date: dc("date"),
+        language: dc("language"),
+        rights: dc("rights"),
+    };
+
+    let keywords = KEYWORD_NAMES
+        .iter()
+        .map(|name| keyword_list(meta_tags, name))
+        .find(|keywords| !keywords.is_empty())
+        .unwrap_or_default();
+
+    PageMetadata {
+        title: first(meta_tags, &TITLE_NAMES).or_else(|| title.map(str::to_string).filter(|title| !title.is_empty())),
+        description: first(meta_tags, &DESCRIPTION_NAMES),
+        image: values(meta_tags, &IMAGE_NAMES).find_map(|value| absolute_url(value, base_url)),
+        author: first(meta_tags, &AUTHOR_NAMES),
+        keywords,
+        published: values(meta_tags, &PUBLISHED_NAMES).find_map(parse_date),
+        modified: values(meta_tags, &MODIFIED_NAMES).find_map(parse_date),
+        open_graph,
+        twitter,
+        dublin_core,
+    }
+}
+
+/// Non-empty values of the tags with any of the names, by order of the names then of the page
+fn values<'a>(meta_tags: &'a [MetaTag], names: &'a [&str]) -> impl Iterator<Item = &'a str> {
+    names.iter().flat_map(move |name| {
+        meta_tags
+            .iter()
+            .filter(move |tag| tag.name.trim().eq_ignore_ascii_case(name))
+            .map(|tag| tag.content.trim())
+            .filter(|content| !content.is_empty())
+    })
+}
+
+fn first(meta_tags: &[MetaTag], names: &[&str]) -> Option<String> {
+    values(meta_tags, names).next().map(str::to_string)
+}
+
+/// Comma or semicolon separated keywords from every tag with the name, without duplicates
+fn keyword_list(meta_tags: &[MetaTag], name: &str) -> Vec<String> {
+    let mut keywords: Vec<String> = Vec::new();
+    for keyword in values(meta_tags, &[name]).flat_map(|value| value.split([',', ';'])) {
+        let keyword = keyword.trim();
+        if !keyword.is_empty() && !keywords.iter().any(|known| known.eq_ignore_ascii_case(keyword)) {
+            keywords.push(keyword.to_string());
+        }
+    }
+    keywords
+}
+
+fn absolute_url(value: &str, base_url: &Url) -> Option<String> {
+    let url = base_url.join(value).ok()?;
+    (url.scheme() == "http" || url.scheme() == "https").then(|| url.to_string())
+}
+
+/// Normalize a date or date-time to RFC 3339, treating values without an offset as UTC
+fn parse_date(value: &str) -> Option<String> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Some(datetime.to_rfc3339());
+    }
+    if let Ok(datetime) = DateTime::parse_from_rfc2822(value) {
+        return Some(datetime.to_rfc3339());
+    }
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+        return Some(datetime.and_utc().to_rfc3339());
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc().to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(pairs: &[(&str, &str)]) -> Vec<MetaTag> {
+        pairs
+            .iter()
+            .map(|(name, content)| MetaTag {
+                name: name.to_string(),
+                content: content.to_string(),
+            })
+            .collect()
+    }
+
+    fn metadata(pairs: &[(&str, &str)], title: Option<&str>) -> PageMetadata {
+        page_metadata(&tags(pairs), title, &Url::parse("https://en.wikipedia.org/wiki/Page").unwrap())
+    }
+
+    #[test]
+    fn title_falls_back_through_vocabularies_to_the_title_element() {
+        let both = metadata(&[("twitter:title", "Twitter"), ("og:title", "Open Graph")], Some("Element"));
+        assert_eq!(both.title.as_deref(), Some("Open Graph"));
+        let blank_og = metadata(&[("og:title", "  "), ("DC.Title", "Dublin Core")], Some("Element"));
+        assert_eq!(blank_og.title.as_deref(), Some("Dublin Core"));
+        assert_eq!(metadata(&[], Some("Element")).title.as_deref(), Some("Element"));
+        assert_eq!(metadata(&[], Some("")).title, None);
+    }
+
+    #[test]
+    fn description_and_author_follow_their_order() {
+        let page = metadata(
+            &[
+                ("og:description", "Open Graph"),
+                ("description", "Plain"),
+                ("twitter:creator", "@writer"),
+                ("dcterms.creator", "Dublin Core"),
+            ],
+            None,
+        );
+        assert_eq!(page.description.as_deref(), Some("Plain"));
+        assert_eq!(page.author.as_deref(), Some("Dublin Core"));
+        assert_eq!(page.twitter.creator.as_deref(), Some("@writer"));
+    }
+
+    #[test]
+    fn image_is_the_first_http_url() {
+        let page = metadata(&[("twitter:image", "/images/card.png"), ("og:image", "data:image/png;base64,AAAA")], None);
+        assert_eq!(page.image.as_deref(), Some("https://en.wikipedia.org/images/card.png"));
+        assert_eq!(page.open_graph.image, None);
+    }
+
+    #[test]
+    fn keywords_come_from_the_first_vocabulary_with_any() {
+        let page = metadata(
+            &[("article:tag", "Search"), ("article:tag", "Index; search"), ("dc.subject", "Ignored"), ("keywords", " ")],
+            None,
+        );
+        assert_eq!(page.keywords, ["Search", "Index"]);
+        let page = metadata(&[("keywords", "a, b,,a"), ("article:tag", "Ignored")], None);
+        assert_eq!(page.keywords, ["a", "b"]);
+    }
+
+    #[test]
+    fn dates_skip_unparseable_values() {
+        let page = metadata(
+            &[
+                ("article:published_time", "last Tuesday"),
+                ("dcterms.created", "2024-01-31"),
+                ("last-modified", "Wed, 31 Jan 2024 12:00:00 GMT"),
+                ("article:modified_time", "2024-02-01T08:30:00"),
+            ],
+            None,
+        );
+        assert_eq!(page.published.as_deref(), Some("2024-01-31T00:00:00+00:00"));
+        assert_eq!(page.modified.as_deref(), Some("2024-02-01T08:30:00+00:00"));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::config::UnicodeForm;
+use crate::extractors::SiteFields;
+use crate::parser::ParsedHtml;
+use crate::structured_data::{Entity, PropertyValue};
+use unicode_normalization::UnicodeNormalization;
+use url::Url;
+
+/// Query parameters that only track the visit and never change the page
+const TRACKING_PARAMS: [&str; 10] = [
+    "utm_source", "utm_medium", "utm_campaign", "utm_term", "utm_content",
+    "fbclid", "gclid", "msclkid", "mc_cid", "mc_eid",
+];
+
+/// Query parameters that number the pages of a paginated series
+/// `p` is left out, as it often names the post or product itself, e.g. WordPress `?p=123`
+const PAGE_PARAMS: [&str; 5] = ["page", "pg", "paged", "start", "offset"];
+
+/// Decode character references in HTML text content, e.g. `&amp;`, `&#8212;`, `&eacute`
+pub fn decode_entities(text: &str) -> String {
+    htmlize::unescape(text).into_owned()
+}
+
+/// Decode character references in an attribute value, where `&name=` is left as written
+pub fn decode_attribute(value: &str) -> String {
+    htmlize::unescape_attribute(value).into_owned()
+}
+
+/// Collapse runs of whitespace into single spaces and trim the ends
+pub fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Canonical form of a URL, used as its key in the crawl store so variants aren't crawled twice
+/// Drops the fragment and tracking parameters, sorts the query and trims a trailing slash from paths
+pub fn normalize_url(url: &str) -> String {
+    let Ok(mut parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+
+    parsed.set_fragment(None);
+
+    let mut query_pairs: Vec<(String, String)> = parsed.query_pairs().into_owned().collect();
+    query_pairs.retain(|(key, _)| !TRACKING_PARAMS.contains(&key.as_str()));
+
+    if query_pairs.is_empty() {
+        parsed.set_query(None);
+    } else {
+        query_pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        let query_string = query_pairs
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+        parsed.set_query(Some(&query_string));
+    }
+
+    let mut normalized = parsed.to_string();
+
+    if normalized.ends_with('/') && normalized.matches('/').count() > 3 {
+        normalized.pop();
+    }
+
+    normalized
+}
+
+/// Key shared by the pages of a paginated series: the normalized URL without its page number
+/// Page number query parameters go, as does a trailing `/page/N` or `/N` path segment
+pub fn pagination_series(url: &str) -> String {
+    let Ok(mut parsed) = Url::parse(&normalize_url(url)) else {
+        return url.to_string();
+    };
+
+    let query_pairs: Vec<(String, String)> = parsed
+        .query_pairs()
+        .into_owned()
+        .filter(|(key, _)| !PAGE_PARAMS.iter().any(|param| key.eq_ignore_ascii_case(param)))
+        .collect();
+    if query_pairs.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(query_pairs);
+    }
+
+    let mut segments: Vec<String> = parsed.path_segments().map(|segments| segments.map(str::to_string).collect()).unwrap_or_default();
+    if segments.last().is_some_and(|segment| segment.is_empty()) {
+        segments.pop();
+    }
+    if segments.len() > 1 && segments.last().is_some_and(|segment| segment.bytes().all(|b| b.is_ascii_digit())) {
+        segments.pop();
+        if segments.last().is_some_and(|segment| segment.eq_ignore_ascii_case("page")) {
+            segments.pop();
+        }
+    }
+    parsed.set_path(&segments.join("/"));
+
+    parsed.to_string()
+}
+
+pub fn normalize_unicode(text: &str, form: UnicodeForm) -> String {
+    match form {
+        UnicodeForm::None => text.to_string(),
+        UnicodeForm::Nfc => text.nfc().collect(),
+        UnicodeForm::Nfkc => text.nfkc().collect(),
+    }
+}
+
+/// Apply a Unicode normalization form to the text fields of a record
+pub fn normalize_record(parsed: &mut ParsedHtml, form: UnicodeForm) {
+    if form == UnicodeForm::None {
+        return;
+    }
+    if let Some(title) = &parsed.title {
+        parsed.title = Some(normalize_unicode(title, form));
+    }
+    for tag in &mut parsed.meta_tags {
+        tag.content = normalize_unicode(&tag.content, form);
+    }
+    let metadata = &mut parsed.metadata;
+    let metadata_text = [
+        &mut metadata.title,
+        &mut metadata.description,
+        &mut metadata.author,
+        &mut metadata.open_graph.title,
+        &mut metadata.open_graph.description,
+        &mut metadata.open_graph.site_name,
+        &mut metadata.twitter.title,
+        &mut metadata.twitter.description,
+        &mut metadata.dublin_core.title,
+        &mut metadata.dublin_core.creator,
+        &mut metadata.dublin_core.subject,
+        &mut metadata.dublin_core.description,
+        &mut metadata.dublin_core.publisher,
+    ];
+    for text in metadata_text.into_iter().flatten() {
+        *text = normalize_unicode(text, form);
+    }
+    for keyword in &mut metadata.keywords {
+        *keyword = normalize_unicode(keyword, form);
+    }
+    for image in &mut parsed.images {
+        for text in [&mut image.alt, &mut image.title, &mut image.caption].into_iter().flatten() {
+            *text = normalize_unicode(text, form);
+        }
+        image.nearby_text = normalize_unicode(&image.nearby_text, form);
+    }
+    for link in &mut parsed.links {
+        link.anchor_text = normalize_unicode(&link.anchor_text, form);
+        if let Some(title) = &link.title {
+            link.title = Some(normalize_unicode(title, form));
+        }
+    }
+    for block in &mut parsed.blocks {
+        block.text = normalize_unicode(&block.text, form);
+    }
+    for entity in &mut parsed.structured_data {
+        normalize_entity(entity, form);
+    }
+    match &mut parsed.site {
+        Some(SiteFields::Wikipedia(wikipedia)) => {
+            for category in &mut wikipedia.categories {
+                *category = normalize_unicode(category, form);
+            }
+            for field in &mut wikipedia.infobox {
+                field.key = normalize_unicode(&field.key, form);
+                field.value = normalize_unicode(&field.value, form);
+            }
+        }
+        None => {}
+    }
+    parsed.main_text = normalize_unicode(&parsed.main_text, form);
+    parsed.full_text = normalize_unicode(&parsed.full_text, form);
+}
+
+fn normalize_entity(entity: &mut Entity, form: UnicodeForm) {
+    for value in entity.properties.values_mut().flatten() {
+        match value {
+            PropertyValue::Text(text) => *text = normalize_unicode(text, form),
+            PropertyValue::Entity(nested) => normalize_entity(nested, form),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::Extractors;
+    use crate::parser::parse_html;
+
+    #[test]
+    fn record_text_agrees_after_nfkc() {
+        let html = r#"<html><body><article>
+            <h2>The ﬁrst １２ rules</h2>
+            <p>A <a href="/wiki/Rule" title="Rule ﬁve">ﬁfth rule</a> numbered １２３ in the book.</p>
+        </article></body></html>"#;
+        let mut parsed = parse_html(html.to_string(), "https://en.wikipedia.org/wiki/Rules", &Extractors::new()).unwrap();
+        normalize_record(&mut parsed, UnicodeForm::Nfkc);
+
+        let block_texts: Vec<&str> = parsed.blocks.iter().map(|block| block.text.as_str()).collect();
+        assert_eq!(block_texts, ["The first 12 rules", "A fifth rule numbered 123 in the book."]);
+        for text in block_texts {
+            assert!(parsed.main_text.contains(text), "{:?} not in {:?}", text, parsed.main_text);
+        }
+
+        let link = &parsed.links[0];
+        assert_eq!(link.anchor_text, "fifth rule");
+        assert_eq!(link.title.as_deref(), Some("Rule five"));
+        assert!(parsed.main_text.contains(&link.anchor_text));
+    }
+}
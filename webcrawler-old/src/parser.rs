@@ -1,75 +1,348 @@
-use lol_html::{element, text, HtmlRewriter, Settings};
+use crate::extractors::{Extractors, SiteFields};
+use crate::feeds::FEED_CONTENT_TYPES;
+use crate::images::{Image, ImageCollector};
+use crate::language::{DetectedLanguage, LanguageAlternate};
+use crate::metadata::{self, PageMetadata};
+use crate::normalize::{self, collapse_whitespace, decode_attribute, decode_entities, normalize_url};
+use crate::readability::{self, BlockCollector};
+use crate::robots;
+use crate::structured_data::{self, Entity, MicrodataCollector};
+use lol_html::errors::RewritingError;
+use lol_html::{element, text, EndTagHandler, HtmlRewriter, MemorySettings, Settings};
 use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
 use url::Url;
 
+/// Hard cap on lol_html's buffering for a single page
+const MAX_PARSER_MEMORY: usize = 16 * 1024 * 1024;
+const PARSER_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Meta refreshes up to this delay redirect; slower ones are links a reader may never follow
+const MAX_REFRESH_REDIRECT_SECS: u64 = 5;
+
+/// Links inside navigation, header and sidebar chrome
+const NAV_LINK_SELECTOR: &str = "nav a[href], header a[href], aside a[href], \
+    [role=navigation] a[href], [role=banner] a[href], [role=complementary] a[href]";
+/// Links inside page footers
+const FOOTER_LINK_SELECTOR: &str = "footer a[href], [role=contentinfo] a[href]";
+
+/// A `<meta>` tag by its `name`, `property` or `http-equiv`
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MetaTag {
     pub name: String,
     pub content: String,
 }
 
+/// Kind of a block in the document outline
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockKind {
+    H1,
+    H2,
+    H3,
+    H4,
+    H5,
+    H6,
+    P,
+    Li,
+    Blockquote,
+    Pre,
+    /// Table cell, header cells included
+    Td,
+}
+
+impl BlockKind {
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let kind = match tag.to_ascii_lowercase().as_str() {
+            "h1" => BlockKind::H1,
+            "h2" => BlockKind::H2,
+            "h3" => BlockKind::H3,
+            "h4" => BlockKind::H4,
+            "h5" => BlockKind::H5,
+            "h6" => BlockKind::H6,
+            "p" => BlockKind::P,
+            "li" => BlockKind::Li,
+            "blockquote" => BlockKind::Blockquote,
+            "pre" => BlockKind::Pre,
+            "td" | "th" => BlockKind::Td,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    pub fn is_heading(self) -> bool {
+        matches!(self, BlockKind::H1 | BlockKind::H2 | BlockKind::H3 | BlockKind::H4 | BlockKind::H5 | BlockKind::H6)
+    }
+}
+
+/// A heading, paragraph, list item, quote, preformatted block or table cell, in document order
 #[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Block {
+    pub kind: BlockKind,
+    pub text: String,
+    /// Element id usable as a URL fragment, for headings also taken from an element inside them
+    pub id: Option<String>,
+    /// Whether the block is part of the main content
+    pub main: bool,
+}
+
+/// Part of the page a link appears in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkContext {
+    /// Navigation, header and sidebar chrome
+    Nav,
+    #[default]
+    Body,
+    Footer,
+}
+
+/// Element a link was found in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// `<a href>`, and hyperlinks in non-HTML documents
+    #[default]
+    Anchor,
+    /// `<iframe src>` or `<frame src>`
+    Frame,
+    /// `<area href>` in an image map
+    Area,
+    /// `<link rel="next">` pagination
+    Next,
+    /// `<link rel="prev">` pagination
+    Prev,
+    /// `<meta http-equiv="refresh">` target
+    Refresh,
+}
+
+/// Link types from the `rel` attribute that affect crawling and ranking
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub struct LinkRel {
+    pub nofollow: bool,
+    /// User-generated content such as comments and forum posts
+    pub ugc: bool,
+    /// Paid or affiliate links
+    pub sponsored: bool,
+}
+
+impl LinkRel {
+    /// Parse a space-separated rel attribute, keeping which of the `robots::NOFOLLOW_RELS` it has
+    pub fn parse(rel: &str) -> Self {
+        if !robots::is_nofollow_rel(rel) {
+            return LinkRel::default();
+        }
+        let has = |name: &str| rel.split_ascii_whitespace().any(|value| value.eq_ignore_ascii_case(name));
+        LinkRel {
+            nofollow: has(robots::NOFOLLOW),
+            ugc: has(robots::UGC),
+            sponsored: has(robots::SPONSORED),
+        }
+    }
+
+    /// Whether the link asks crawlers not to follow it
+    pub fn is_nofollow(self) -> bool {
+        self.nofollow || self.ugc || self.sponsored
+    }
+}
+
+/// An outlink with what the page says about its target
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Link {
+    pub url: String,
+    pub kind: LinkKind,
+    /// Text of the link, including the alt text of images inside it
+    pub anchor_text: String,
+    pub title: Option<String>,
+    pub rel: LinkRel,
+    /// Whether the target is on the page's own host, ignoring a `www.` prefix
+    pub internal: bool,
+    pub context: LinkContext,
+}
+
+impl Link {
+    /// A link in the body of a page with no anchor text or attributes yet
+    pub fn new(url: String, page_url: &str) -> Self {
+        let internal = match (Url::parse(&url), Url::parse(page_url)) {
+            (Ok(target), Ok(page)) => site_host(&target) == site_host(&page),
+            _ => false,
+        };
+        Self {
+            url,
+            kind: LinkKind::Anchor,
+            anchor_text: String::new(),
+            title: None,
+            rel: LinkRel::default(),
+            internal,
+            context: LinkContext::Body,
+        }
+    }
+}
+
+fn site_host(url: &Url) -> Option<String> {
+    let host = url.host_str()?.to_ascii_lowercase();
+    Some(host.strip_prefix("www.").map(str::to_string).unwrap_or(host))
+}
+
+/// Place of a page in a series linked by `rel="next"` and `rel="prev"`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Pagination {
+    /// Key shared by the pages of the series, see `normalize::pagination_series`
+    /// None when the linked pages don't share the page's key, so the links may not be a series at all
+    pub series: Option<String>,
+    pub prev: Option<String>,
+    pub next: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ParsedHtml {
     pub url: String,
+    pub content_type: String,
+    /// Declared by `<html lang>` or the document, else detected from the text when the detection is reliable
     pub language: Option<String>,
+    pub detected_language: Option<DetectedLanguage>,
+    /// Editions of the page in other languages
+    pub alternates: Vec<LanguageAlternate>,
     pub title: Option<String>,
     pub meta_tags: Vec<MetaTag>,
+    pub metadata: PageMetadata,
     pub canonical_url: Option<String>,
-    pub content_text: String,
-    pub links: Vec<String>,
-}
-
-/// Check if URL points to a media file that shouldn't be crawled
-fn is_media_file(url: &str) -> bool {
-    let media_extensions = [
-        // Images
-        ".jpg", ".jpeg", ".png", ".gif", ".bmp", ".svg", ".webp", ".ico", ".tiff",
-        // Videos
-        ".mp4", ".avi", ".mov", ".wmv", ".flv", ".webm", ".mkv", ".m4v",
-        // Audio
-        ".mp3", ".wav", ".ogg", ".m4a", ".flac", ".aac",
-        // Documents
-        ".pdf", ".doc", ".docx", ".xls", ".xlsx", ".ppt", ".pptx", ".xml",
-        // Archives
-        ".zip", ".rar", ".tar", ".gz", ".7z",
-        // Executables
-        ".exe", ".dmg", ".pkg", ".deb", ".rpm",
-    ];
-    
-    let url_lower = url.to_lowercase();
-    // Check path component for extension (ignore query params)
-    let path = url_lower.split('?').next().unwrap_or(&url_lower);
-    media_extensions.iter().any(|ext| path.ends_with(ext))
+    /// Text of the main content without navigation and page chrome; all text for plain documents
+    pub main_text: String,
+    /// All visible text
+    pub full_text: String,
+    /// Typed blocks of the page in document order
+    pub blocks: Vec<Block>,
+    /// Outlinks in document order, including ones marked nofollow
+    pub links: Vec<Link>,
+    /// Target of a meta refresh that redirects right away; the page itself is not indexed
+    pub redirect: Option<String>,
+    pub pagination: Option<Pagination>,
+    /// Images with their descriptions, written to the image stream rather than with the page
+    #[serde(skip)]
+    pub images: Vec<Image>,
+    pub feeds: Vec<String>,
+    /// Schema.org entities from JSON-LD scripts and microdata
+    pub structured_data: Vec<Entity>,
+    /// JSON-LD scripts that were not valid JSON and were skipped
+    pub invalid_json_ld: usize,
+    /// Fields from the site-specific extractor that handled the page, if any
+    pub site: Option<SiteFields>,
+    /// Fingerprint of the main text, equal for exact duplicates, see `dedup::content_hash`
+    pub content_hash: Option<String>,
+    /// URL of the page this one duplicates; duplicates are recorded as its aliases and not written
+    #[serde(skip)]
+    pub duplicate_of: Option<String>,
+    /// SimHash fingerprint of the main text in hex, see `dedup::simhash`
+    pub simhash: Option<String>,
+    /// Near-duplicate cluster, named by the URL of its first page; the index can collapse pages sharing one
+    pub cluster: Option<String>,
+    /// Robots directives from meta tags and the X-Robots-Tag header
+    pub robots: Vec<String>,
+    /// Milliseconds spent resolving the host, None when unknown
+    pub dns_ms: Option<u64>,
 }
 
 fn url_validation(url: Url) -> bool {
     let scheme = url.scheme();
     let host = url.host_str();
     let domain = url.domain();
-    if is_media_file(url.as_str()) {
+    if let Some(d) = domain
+        && !d.ends_with("wikipedia.org") {
         return false;
     }
-    if let Some(d) = domain {
-        if !d.ends_with("wikipedia.org") {
-            return false;
-        }
+    (scheme == "http" || scheme == "https") && host.is_some()
+}
+
+/// A link to a crawlable URL given by a raw attribute value, resolved against the document base
+fn resolve_link(href: Option<String>, document_base: &Url, page_url: &str) -> Option<Link> {
+    let target = document_base.join(decode_attribute(&href?).trim()).ok()?;
+    url_validation(target.clone()).then(|| Link::new(target.to_string(), page_url))
+}
+
+/// Parse a refresh value such as `0; url=/next` into its delay and target, None when it only reloads the page
+fn parse_refresh(content: &str) -> Option<(u64, String)> {
+    let (delay, target) = content.split_once([';', ','])?;
+    let delay = delay.trim().split('.').next()?.parse::<u64>().ok()?;
+
+    let target = target.trim();
+    let target = match target.get(..3) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("url") => target[3..].trim_start().strip_prefix('=')?.trim(),
+        _ => target,
+    };
+    let target = target.trim_matches(['\'', '"']).trim();
+    (!target.is_empty()).then(|| (delay, target.to_string()))
+}
+
+/// The page's series when it links to a next or previous page
+fn pagination(links: &[Link], page_url: &str) -> Option<Pagination> {
+    let find = |kind: LinkKind| links.iter().find(|link| link.kind == kind).map(|link| link.url.clone());
+    let (prev, next) = (find(LinkKind::Prev), find(LinkKind::Next));
+    if prev.is_none() && next.is_none() {
+        return None;
     }
-    return (scheme == "http" || scheme == "https") && host.is_some();
+    let series = normalize::pagination_series(page_url);
+    let same_series = prev.iter().chain(&next).all(|url| normalize::pagination_series(url) == series);
+    Some(Pagination {
+        series: same_series.then_some(series),
+        prev,
+        next,
+    })
+}
+
+fn is_http(url: &Url) -> bool {
+    url.scheme() == "http" || url.scheme() == "https"
+}
+
+/// Check if an absolute URL from outside the page (e.g. a sitemap) is in crawl scope
+pub fn is_crawlable(url: &str) -> bool {
+    Url::parse(url).map(url_validation).unwrap_or(false)
 }
 
-pub fn parse_html(input: String, base_url: &str) -> ParsedHtml {
-    let base_url_parsed = Url::parse(base_url).expect("Failed to parse base URL");
+/// Parse an HTML page, adding the fields of the site-specific extractor for its URL
+pub fn parse_html(input: String, base_url: &str, extractors: &Extractors) -> Result<ParsedHtml, ParseError> {
+    let base_url_parsed = Url::parse(base_url).map_err(ParseError::InvalidBaseUrl)?;
+    // Relative URLs resolve against the first <base href>, or the page URL without one
+    // Only URLs after the <base> element see it, which is where pages put it
+    let document_base = RefCell::new(base_url_parsed.clone());
+    let base_seen = Cell::new(false);
 
-    let mut links = Vec::new();
+    let links: RefCell<Vec<Link>> = RefCell::new(Vec::new());
+    // Index of the link whose anchor text is being collected, None while inside an invalid link
+    let current_link = Cell::new(None);
+    let link_context = Cell::new(LinkContext::Body);
+    let mut feeds = Vec::new();
+    let mut alternates = Vec::new();
     let mut meta_tags = Vec::new();
     let mut title = None;
     let mut language = None;
     let mut canonical_url = None;
-    let mut content_text = String::new();
+    let mut redirect = None;
+    let blocks = Rc::new(RefCell::new(BlockCollector::new()));
+    let json_ld_scripts: RefCell<Vec<String>> = RefCell::new(Vec::new());
+    // End tag handlers must be 'static, so the microdata collector is shared with them through an Rc
+    let microdata = Rc::new(RefCell::new(MicrodataCollector::new(base_url_parsed.clone())));
+    let images = RefCell::new(ImageCollector::new(base_url_parsed.clone()));
+    let site_page = extractors.find(&base_url_parsed).map(|extractor| extractor.page(&base_url_parsed));
+    let site_handlers = site_page.as_ref().map(|page| page.handlers()).unwrap_or_default();
 
     let mut rewriter = HtmlRewriter::new(
         Settings {
             element_content_handlers: vec![
+                element!("base[href]", |el| {
+                    if base_seen.replace(true) {
+                        return Ok(());
+                    }
+                    if let Some(href) = el.get_attribute("href")
+                        && let Ok(base) = base_url_parsed.join(decode_attribute(&href).trim())
+                        && is_http(&base) {
+                        microdata.borrow_mut().set_base_url(base.clone());
+                        images.borrow_mut().set_base_url(base.clone());
+                        *document_base.borrow_mut() = base;
+                    }
+                    Ok(())
+                }),
                 // Extract language from html tag
                 element!("html[lang]", |el| {
                     if let Some(lang) = el.get_attribute("lang") {
@@ -87,62 +360,475 @@ pub fn parse_html(input: String, base_url: &str) -> ParsedHtml {
                     Ok(())
                 }),
                 // Extract meta tags
-                element!("meta[name][content]", |el| {
-                    if let (Some(name), Some(content)) = (el.get_attribute("name"), el.get_attribute("content")) {
-                        meta_tags.push(MetaTag { name, content });
+                // OpenGraph uses property= rather than name=
+                element!("meta[content]", |el| {
+                    let name = el
+                        .get_attribute("name")
+                        .or_else(|| el.get_attribute("property"))
+                        .or_else(|| el.get_attribute("http-equiv"));
+                    if let (Some(name), Some(content)) = (name, el.get_attribute("content")) {
+                        meta_tags.push(MetaTag {
+                            name: decode_attribute(&name),
+                            content: decode_attribute(&content),
+                        });
                     }
                     Ok(())
                 }),
-                // Extract canonical URL
+                // Extract canonical URL, in the same form the URL store keys pages by
                 element!("link[rel=canonical]", |el| {
-                    if let Some(href) = el.get_attribute("href") {
-                        canonical_url = Some(href);
+                    if let Some(href) = el.get_attribute("href")
+                        && let Ok(canonical) = document_base.borrow().join(decode_attribute(&href).trim())
+                        && is_http(&canonical) {
+                        canonical_url = Some(normalize_url(canonical.as_str()));
+                    }
+                    Ok(())
+                }),
+                // Extract RSS/Atom feeds advertised by the page
+                element!("link[rel~=alternate][type][href]", |el| {
+                    if let (Some(content_type), Some(href)) = (el.get_attribute("type"), el.get_attribute("href"))
+                        && FEED_CONTENT_TYPES.contains(&content_type.trim().to_ascii_lowercase().as_str())
+                        && let Ok(feed_url) = document_base.borrow().join(&decode_attribute(&href))
+                        && is_http(&feed_url) {
+                        feeds.push(feed_url.to_string());
                     }
                     Ok(())
                 }),
+                // Extract language editions of the page
+                element!("link[rel~=alternate][hreflang][href]", |el| {
+                    if let (Some(hreflang), Some(href)) = (el.get_attribute("hreflang"), el.get_attribute("href"))
+                        && let Ok(url) = document_base.borrow().join(decode_attribute(&href).trim())
+                        && is_http(&url) {
+                        alternates.push(LanguageAlternate {
+                            hreflang: decode_attribute(&hreflang).trim().to_string(),
+                            url: url.to_string(),
+                        });
+                    }
+                    Ok(())
+                }),
+                // Classify links by the page chrome they sit in, before they are extracted below
+                element!(NAV_LINK_SELECTOR, |_| {
+                    link_context.set(LinkContext::Nav);
+                    Ok(())
+                }),
+                element!(FOOTER_LINK_SELECTOR, |_| {
+                    link_context.set(LinkContext::Footer);
+                    Ok(())
+                }),
                 // Extract links
                 element!("a[href]", |el| {
-                    if let Some(attached_url) = el.get_attribute("href") {
-                        if let Ok(parsed_url) = Url::parse(&attached_url) {
-                            if url_validation(parsed_url.clone()) {
-                                links.push(parsed_url.to_string());
-                            }
-                            return Ok(());
-                        }
-                        if let Ok(joined_url) = base_url_parsed.join(&attached_url) {
-                            if url_validation(joined_url.clone()) {
-                                links.push(joined_url.to_string());
-                            }
-                        }
+                    let context = link_context.replace(LinkContext::Body);
+                    current_link.set(None);
+                    if let Some(mut link) = resolve_link(el.get_attribute("href"), &document_base.borrow(), base_url) {
+                        link.title = el.get_attribute("title").map(|title| collapse_whitespace(&decode_attribute(&title)));
+                        link.rel = el.get_attribute("rel").map(|rel| LinkRel::parse(&rel)).unwrap_or_default();
+                        link.context = context;
+                        let mut links = links.borrow_mut();
+                        current_link.set(Some(links.len()));
+                        links.push(link);
+                    }
+                    Ok(())
+                }),
+                // Frames, image maps and pagination are linked pages too
+                element!("iframe[src], frame[src]", |el| {
+                    if let Some(mut link) = resolve_link(el.get_attribute("src"), &document_base.borrow(), base_url) {
+                        link.kind = LinkKind::Frame;
+                        link.title = el.get_attribute("title").map(|title| collapse_whitespace(&decode_attribute(&title)));
+                        links.borrow_mut().push(link);
+                    }
+                    Ok(())
+                }),
+                element!("area[href]", |el| {
+                    if let Some(mut link) = resolve_link(el.get_attribute("href"), &document_base.borrow(), base_url) {
+                        link.kind = LinkKind::Area;
+                        link.anchor_text = el.get_attribute("alt").unwrap_or_default();
+                        link.title = el.get_attribute("title").map(|title| collapse_whitespace(&decode_attribute(&title)));
+                        link.rel = el.get_attribute("rel").map(|rel| LinkRel::parse(&rel)).unwrap_or_default();
+                        links.borrow_mut().push(link);
                     }
                     Ok(())
                 }),
-                // Extract text content from body
-                text!("body *", |t| {
-                    if !t.as_str().trim().is_empty() {
-                        if !content_text.is_empty() {
-                            content_text.push(' ');
+                element!("link[rel~=next][href], link[rel~=prev][href], link[rel~=previous][href]", |el| {
+                    let rel = el.get_attribute("rel").unwrap_or_default().to_ascii_lowercase();
+                    if let Some(mut link) = resolve_link(el.get_attribute("href"), &document_base.borrow(), base_url) {
+                        link.kind = if rel.split_ascii_whitespace().any(|value| value == "next") {
+                            LinkKind::Next
+                        } else {
+                            LinkKind::Prev
+                        };
+                        links.borrow_mut().push(link);
+                    }
+                    Ok(())
+                }),
+                element!("meta[http-equiv][content]", |el| {
+                    let is_refresh = el.get_attribute("http-equiv").is_some_and(|name| name.trim().eq_ignore_ascii_case("refresh"));
+                    if is_refresh
+                        && let Some((delay, target)) = el.get_attribute("content").and_then(|content| parse_refresh(&decode_attribute(&content)))
+                        && let Some(mut link) = resolve_link(Some(target), &document_base.borrow(), base_url) {
+                        link.kind = LinkKind::Refresh;
+                        if delay <= MAX_REFRESH_REDIRECT_SECS && redirect.is_none() {
+                            redirect = Some(link.url.clone());
                         }
-                        content_text.push_str(t.as_str().trim());
+                        links.borrow_mut().push(link);
+                    }
+                    Ok(())
+                }),
+                // Anchor text, kept raw until the page is done as an entity may span chunks
+                text!("a[href]", |t| {
+                    if let Some(index) = current_link.get() {
+                        links.borrow_mut()[index].anchor_text.push_str(t.as_str());
                     }
                     Ok(())
                 }),
-            ],
+                element!("a[href] img[alt]", |el| {
+                    if let (Some(index), Some(alt)) = (current_link.get(), el.get_attribute("alt")) {
+                        let anchor_text = &mut links.borrow_mut()[index].anchor_text;
+                        anchor_text.push(' ');
+                        anchor_text.push_str(&alt);
+                        anchor_text.push(' ');
+                    }
+                    Ok(())
+                }),
+                // Structured data
+                element!(structured_data::JSON_LD_SELECTOR, |_| {
+                    json_ld_scripts.borrow_mut().push(String::new());
+                    Ok(())
+                }),
+                text!(structured_data::JSON_LD_SELECTOR, |t| {
+                    if let Some(script) = json_ld_scripts.borrow_mut().last_mut() {
+                        script.push_str(t.as_str());
+                    }
+                    Ok(())
+                }),
+                element!(structured_data::MICRODATA_SELECTOR, |el| {
+                    let serial = microdata.borrow_mut().open(el);
+                    if let Some(serial) = serial
+                        && let Some(handlers) = el.end_tag_handlers() {
+                        let microdata = microdata.clone();
+                        handlers.push(Box::new(move |_| {
+                            microdata.borrow_mut().close(serial);
+                            Ok(())
+                        }));
+                    }
+                    Ok(())
+                }),
+                text!(structured_data::MICRODATA_SELECTOR, |t| {
+                    microdata.borrow_mut().push_text(t.as_str());
+                    Ok(())
+                }),
+                // Images, with the caption of the figure they are in
+                element!("figure", |_| {
+                    images.borrow_mut().open_figure();
+                    Ok(())
+                }),
+                text!("figcaption", |t| {
+                    images.borrow_mut().push_caption(t.as_str());
+                    Ok(())
+                }),
+                element!("figure img", |_| {
+                    images.borrow_mut().mark_in_figure();
+                    Ok(())
+                }),
+                element!("img", |el| {
+                    images.borrow_mut().add_image(el);
+                    Ok(())
+                }),
+                // Classify each text chunk before it reaches the collector below
+                text!(readability::HIDDEN_SELECTOR, |_| {
+                    blocks.borrow_mut().mark_hidden();
+                    Ok(())
+                }),
+                text!("a[href]", |_| {
+                    blocks.borrow_mut().mark_link();
+                    Ok(())
+                }),
+                text!(readability::BOILERPLATE_SELECTOR, |_| {
+                    blocks.borrow_mut().mark_boilerplate();
+                    Ok(())
+                }),
+                text!(readability::HEADER_FOOTER_SELECTOR, |_| {
+                    blocks.borrow_mut().mark_header_footer();
+                    Ok(())
+                }),
+                text!(readability::SECTION_HEADER_FOOTER_SELECTOR, |_| {
+                    blocks.borrow_mut().mark_section_header_footer();
+                    Ok(())
+                }),
+                text!(readability::MAIN_SELECTOR, |_| {
+                    blocks.borrow_mut().mark_main();
+                    Ok(())
+                }),
+                element!(readability::BLOCK_SELECTOR, |el| {
+                    let id = el.get_attribute("id").map(|id| decode_attribute(&id));
+                    blocks.borrow_mut().start_block(BlockKind::from_tag(&el.tag_name()), id);
+                    // Text after the end tag belongs to the enclosing element, not to this block
+                    if let Some(handlers) = el.end_tag_handlers() {
+                        let blocks = blocks.clone();
+                        let handler: EndTagHandler<'static> = Box::new(move |_| {
+                            blocks.borrow_mut().end_element();
+                            Ok(())
+                        });
+                        handlers.push(handler);
+                    }
+                    // Keep words of adjacent blocks apart in the text around images
+                    images.borrow_mut().push_text(" ");
+                    Ok(())
+                }),
+                // Older markup puts heading anchors on an element inside the heading
+                element!("h1 [id], h2 [id], h3 [id], h4 [id], h5 [id], h6 [id]", |el| {
+                    if let Some(id) = el.get_attribute("id") {
+                        blocks.borrow_mut().note_heading_id(decode_attribute(&id));
+                    }
+                    Ok(())
+                }),
+                // Collect visible text into blocks
+                text!("*", |t| {
+                    if blocks.borrow_mut().push_text(t.as_str()) {
+                        images.borrow_mut().push_text(t.as_str());
+                    }
+                    Ok(())
+                }),
+            ]
+            .into_iter()
+            // Site-specific handlers run after the generic ones
+            .chain(site_handlers)
+            .collect(),
+            memory_settings: MemorySettings {
+                preallocated_parsing_buffer_size: PARSER_BUFFER_SIZE,
+                max_allowed_memory_usage: MAX_PARSER_MEMORY,
+            },
+            // Lenient mode never bails out on ambiguous markup
+            strict: false,
             ..Settings::new()
         },
         |_: &[u8]| {}
     );
 
-    rewriter.write(input.as_bytes()).expect("Failed to parse HTML input");
-    rewriter.end().expect("Failed to complete HTML parsing");
+    rewriter.write(input.as_bytes())?;
+    rewriter.end()?;
+
+    let blocks = blocks.take().finish();
+    let mut links = links.into_inner();
+    for link in &mut links {
+        link.anchor_text = collapse_whitespace(&decode_entities(&link.anchor_text));
+    }
+    // Title chunks are raw source text, decoded once the whole title is known
+    let title = title.map(|title| collapse_whitespace(&decode_entities(&title)));
+
+    let mut structured_data = Vec::new();
+    let mut invalid_json_ld = 0;
+    for script in json_ld_scripts.into_inner() {
+        match structured_data::parse_json_ld(&script) {
+            Some(entities) => structured_data.extend(entities),
+            None => invalid_json_ld += 1,
+        }
+    }
+    structured_data.extend(microdata.borrow_mut().finish());
+
+    let pagination = pagination(&links, canonical_url.as_deref().unwrap_or(base_url));
+
+    let metadata = metadata::page_metadata(&meta_tags, title.as_deref(), &document_base.borrow());
 
-    ParsedHtml {
+    let mut robots_directives: Vec<String> = meta_tags
+        .iter()
+        .filter(|tag| robots::is_robots_meta(&tag.name))
+        .flat_map(|tag| robots::parse_directives(&tag.content))
+        .collect();
+    robots_directives.sort();
+    robots_directives.dedup();
+
+    let mut parsed = ParsedHtml {
         url: base_url.to_string(),
+        content_type: "text/html".to_string(),
         language,
+        detected_language: None,
+        alternates,
         title,
         meta_tags,
+        metadata,
         canonical_url,
-        content_text,
+        main_text: readability::main_text(&blocks),
+        full_text: readability::full_text(&blocks),
+        blocks: readability::outline(&blocks),
         links,
+        redirect,
+        pagination,
+        images: images.into_inner().finish(),
+        feeds,
+        structured_data,
+        invalid_json_ld,
+        site: None,
+        content_hash: None,
+        duplicate_of: None,
+        simhash: None,
+        cluster: None,
+        robots: robots_directives,
+        dns_ms: None,
+    };
+    if let Some(page) = site_page {
+        page.finish(&mut parsed);
+    }
+    Ok(parsed)
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    InvalidBaseUrl(url::ParseError),
+    MemoryLimitExceeded,
+    Rewriting(String),
+}
+
+impl From<RewritingError> for ParseError {
+    fn from(err: RewritingError) -> Self {
+        match err {
+            RewritingError::MemoryLimitExceeded(_) => ParseError::MemoryLimitExceeded,
+            other => ParseError::Rewriting(other.to_string()),
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::InvalidBaseUrl(e) => write!(f, "Invalid base URL: {}", e),
+            ParseError::MemoryLimitExceeded => write!(f, "HTML parser memory limit exceeded"),
+            ParseError::Rewriting(e) => write!(f, "HTML parsing failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    const PAGE_URL: &str = "https://example.org/articles/hostile";
+    /// Larger than the parser's memory limit
+    const HUGE: usize = MAX_PARSER_MEMORY + 1024 * 1024;
+
+    #[derive(Debug)]
+    enum Expect {
+        Ok,
+        /// Parsed, with this in the page text
+        Text(&'static str),
+        MemoryLimitExceeded,
+    }
+
+    fn parse(input: String) -> Result<ParsedHtml, ParseError> {
+        parse_html(input, PAGE_URL, &Extractors::with_defaults())
+    }
+
+    fn hostile_corpus() -> Vec<(&'static str, String, Expect)> {
+        let paragraph = "<p>Plain words of an ordinary paragraph, repeated to fill the page.</p>\n";
+        vec![
+            ("empty", String::new(), Expect::Ok),
+            ("unclosed tags", "<html><body><div><p><a href='/a'><b><i>text <table><tr><td>cell".repeat(1000), Expect::Ok),
+            ("stray end tags", "</p></div></table></html></body>text</a></script>".repeat(1000), Expect::Ok),
+            ("unclosed comment", format!("<p>before</p><!-- {}", "x".repeat(1024 * 1024)), Expect::Text("before")),
+            ("unclosed attribute quote", format!("<a href=\"/a {}", "y".repeat(1024 * 1024)), Expect::Ok),
+            ("unclosed script", format!("<script>{}", "var x = '<p>';".repeat(100_000)), Expect::Ok),
+            ("unclosed title", format!("<title>{}", "t".repeat(1024 * 1024)), Expect::Ok),
+            (
+                "deep nesting",
+                format!("{}text{}", "<div><span>".repeat(1000), "</span></div>".repeat(1000)),
+                Expect::Ok,
+            ),
+            // The open element stack kept for selector matching outgrows the memory limit
+            (
+                "extreme nesting",
+                format!("{}text{}", "<div><span>".repeat(100_000), "</span></div>".repeat(100_000)),
+                Expect::MemoryLimitExceeded,
+            ),
+            ("deep unclosed lists", "<ul><li>item".repeat(1000), Expect::Ok),
+            (
+                "broken entities",
+                "<p title='&#xFFFFFFFF;&#0;&;&#x;'>&amp &lt;&gt &#55296; &#x110000; &#0; &#; &#x; &nosuchentity; &&&; &#9999999999999;</p>"
+                    .repeat(1000),
+                Expect::Ok,
+            ),
+            (
+                "entities in links",
+                "<a href='/a?x=1&amp&#x;y=&#0;'>&#x1F600;&#xD800;</a><img src='&#;' srcset='&amp;, &#x2c; 2x'>".repeat(1000),
+                Expect::Ok,
+            ),
+            ("control characters", "<p>\u{0}\u{1}\u{b}\u{c}\u{7f}\u{feff}\u{fffd}</p>".repeat(1000), Expect::Ok),
+            ("many attributes", format!("<div {}>text</div>", "a=b ".repeat(100_000)), Expect::Ok),
+            ("malformed json-ld", r#"<script type="application/ld+json">{"@type": [[[[</script>"#.repeat(1000), Expect::Ok),
+            ("huge attribute", format!("<a href=\"{}\">link</a>", "a".repeat(HUGE)), Expect::Ok),
+            ("huge tag name", format!("<{}>", "x".repeat(HUGE)), Expect::Ok),
+            ("huge text", paragraph.repeat(HUGE / paragraph.len() + 1), Expect::Text("ordinary paragraph")),
+            ("huge script", format!("<script>{}</script><p>after</p>", "s".repeat(HUGE)), Expect::Text("after")),
+            ("huge comment", format!("<!--{}--><p>after</p>", "c".repeat(HUGE)), Expect::Text("after")),
+        ]
+    }
+
+    #[test]
+    fn relative_urls_resolve_against_the_first_base() {
+        let html = r#"<html><head>
+            <link rel="alternate" hreflang="de" href="early">
+            <base href="https://en.wikipedia.org/wiki/Base/">
+            <base href="https://en.wikipedia.org/ignored/">
+            <link rel="alternate" type="application/rss+xml" href="feed.xml">
+            <link rel="alternate" hreflang="fr" href="/wiki/Fr">
+        </head><body>
+            <a href="child">Child</a>
+            <a href="../Sibling">Sibling</a>
+            <img src="picture.png" alt="Picture">
+        </body></html>"#;
+        let parsed = parse_html(html.to_string(), "https://en.wikipedia.org/wiki/Page", &Extractors::new()).unwrap();
+
+        let alternates: Vec<&str> = parsed.alternates.iter().map(|alternate| alternate.url.as_str()).collect();
+        assert_eq!(alternates, ["https://en.wikipedia.org/wiki/early", "https://en.wikipedia.org/wiki/Fr"]);
+        assert_eq!(parsed.feeds, ["https://en.wikipedia.org/wiki/Base/feed.xml"]);
+        let links: Vec<&str> = parsed.links.iter().map(|link| link.url.as_str()).collect();
+        assert_eq!(links, ["https://en.wikipedia.org/wiki/Base/child", "https://en.wikipedia.org/wiki/Sibling"]);
+        assert_eq!(parsed.images[0].url, "https://en.wikipedia.org/wiki/Base/picture.png");
+    }
+
+    #[test]
+    fn non_http_base_is_ignored() {
+        let html = r#"<base href="javascript:alert(1)"><a href="/wiki/Child">Child</a>"#;
+        let parsed = parse_html(html.to_string(), "https://en.wikipedia.org/wiki/Page", &Extractors::new()).unwrap();
+        assert_eq!(parsed.links[0].url, "https://en.wikipedia.org/wiki/Child");
+    }
+
+    #[test]
+    fn pagination_series_needs_matching_links() {
+        let series = |url: &str, links: &str| {
+            let parsed = parse_html(links.to_string(), url, &Extractors::new()).unwrap();
+            parsed.pagination.and_then(|pagination| pagination.series)
+        };
+
+        let log = "https://en.wikipedia.org/wiki/Special:Log?offset=20";
+        let links = r#"<link rel="prev" href="/wiki/Special:Log?offset=0"><link rel="next" href="/wiki/Special:Log?offset=40">"#;
+        assert_eq!(series(log, links).as_deref(), Some("https://en.wikipedia.org/wiki/Special:Log"));
+
+        let archive = "https://en.wikipedia.org/wiki/Archive/page/2/";
+        let links = r#"<link rel="next" href="/wiki/Archive/page/3/">"#;
+        assert_eq!(series(archive, links).as_deref(), Some("https://en.wikipedia.org/wiki/Archive"));
+
+        // Next and previous posts are separate pages, not a series
+        let post = "https://en.wikipedia.org/w/index.php?p=123";
+        let links = r#"<link rel="prev" href="/w/index.php?p=122"><link rel="next" href="/w/index.php?p=124">"#;
+        assert_eq!(series(post, links), None);
+
+        let listing = "https://en.wikipedia.org/w/index.php?title=Talk&page=2";
+        let links = r#"<link rel="prev" href="/w/index.php?title=Talk&page=1"><link rel="next" href="/w/index.php?title=Talk&page=3">"#;
+        assert_eq!(series(listing, links).as_deref(), Some("https://en.wikipedia.org/w/index.php?title=Talk"));
+        assert_eq!(series(log, r#"<link rel="next" href="/wiki/Special:Contributions?offset=40">"#), None);
+    }
+
+    #[test]
+    fn hostile_html_parses_or_fails_cleanly() {
+        for (name, input, expected) in hostile_corpus() {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| parse(input)))
+                .unwrap_or_else(|_| panic!("{}: parse_html panicked", name));
+            match (&expected, &result) {
+                (Expect::Ok, Ok(_)) | (Expect::MemoryLimitExceeded, Err(ParseError::MemoryLimitExceeded)) => {}
+                (Expect::Text(text), Ok(parsed)) if parsed.full_text.contains(text) => {}
+                _ => panic!("{}: expected {:?}, got {:?}", name, expected, result.map(|parsed| parsed.full_text.len())),
+            }
+        }
     }
 }
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use url::Url;
+
+/// Observations of one pattern needed before its verdict is trusted
+const MIN_OBSERVATIONS: u32 = 3;
+const MAX_PATTERNS: usize = 100_000;
+
+/// Extensions that usually mean a non-document download
+const BINARY_EXTENSIONS: [&str; 33] = [
+    // Images
+    "jpg", "jpeg", "png", "gif", "bmp", "svg", "webp", "ico", "tiff",
+    // Videos
+    "mp4", "avi", "mov", "wmv", "flv", "webm", "mkv", "m4v",
+    // Audio
+    "mp3", "wav", "ogg", "m4a", "flac", "aac",
+    // Archives
+    "zip", "rar", "tar", "gz", "7z",
+    // Executables
+    "exe", "dmg", "pkg", "deb", "rpm",
+];
+
+/// Path segments typical of endpoints that serve files
+const DOWNLOAD_SEGMENTS: [&str; 5] = ["download", "downloads", "attachment", "export", "getfile"];
+
+/// What the cache has learned about a URL's pattern
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    /// Responses for this pattern have always been accepted
+    Fetch,
+    /// Responses for this pattern have always been rejected, with the last type seen
+    Skip(String),
+    Unknown,
+}
+
+#[derive(Debug, Default)]
+struct PatternStats {
+    accepted: u32,
+    rejected: u32,
+    last_rejected_type: String,
+}
+
+/// Content types learned per host and path pattern
+pub struct ContentTypeCache {
+    patterns: Mutex<HashMap<String, PatternStats>>,
+}
+
+impl ContentTypeCache {
+    pub fn new() -> Self {
+        Self {
+            patterns: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn verdict(&self, url: &str) -> Verdict {
+        let Some(pattern) = path_pattern(url) else {
+            return Verdict::Unknown;
+        };
+
+        let patterns = self.patterns.lock().unwrap();
+        match patterns.get(&pattern) {
+            Some(stats) if stats.accepted == 0 && stats.rejected >= MIN_OBSERVATIONS => {
+                Verdict::Skip(stats.last_rejected_type.clone())
+            }
+            Some(stats) if stats.rejected == 0 && stats.accepted >= MIN_OBSERVATIONS => Verdict::Fetch,
+            _ => Verdict::Unknown,
+        }
+    }
+
+    /// Record whether a response of `mime_type` for `url` was accepted
+    pub fn record(&self, url: &str, mime_type: &str, accepted: bool) {
+        let Some(pattern) = path_pattern(url) else {
+            return;
+        };
+
+        let mut patterns = self.patterns.lock().unwrap();
+        if patterns.len() >= MAX_PATTERNS && !patterns.contains_key(&pattern) {
+            // Make room by forgetting patterns that never reached a verdict, keeping what was learned
+            patterns.retain(|_, stats| stats.accepted + stats.rejected >= MIN_OBSERVATIONS);
+            if patterns.len() >= MAX_PATTERNS {
+                patterns.clear();
+            }
+        }
+
+        let stats = patterns.entry(pattern).or_default();
+        if accepted {
+            stats.accepted += 1;
+        } else {
+            stats.rejected += 1;
+            stats.last_rejected_type = mime_type.to_string();
+        }
+    }
+}
+
+/// Generalize a URL so that URLs served by the same handler share a key
+/// `https://a.org/files/123/report.PDF?id=5&v=2` becomes `a.org/files/*/*.pdf?id&v`
+/// and `https://en.wikipedia.org/wiki/Rust` becomes `en.wikipedia.org/wiki/*`
+pub fn path_pattern(url: &str) -> Option<String> {
+    let parsed = Url::parse(url).ok()?;
+    let host = parsed.host_str()?;
+    let segments: Vec<&str> = parsed.path_segments()?.collect();
+    let (last, dirs) = segments.split_last()?;
+
+    let mut pattern = host.to_string();
+    for dir in dirs {
+        pattern.push('/');
+        pattern.push_str(if dir.chars().any(|c| c.is_ascii_digit()) { "*" } else { dir });
+    }
+
+    // Leaf segments are usually titles or ids, so only their extension is kept
+    pattern.push('/');
+    match extension(last) {
+        Some(ext) => {
+            pattern.push_str("*.");
+            pattern.push_str(&ext);
+        }
+        None if last.is_empty() => {}
+        None => pattern.push('*'),
+    }
+
+    let mut keys: Vec<String> = parsed.query_pairs().map(|(key, _)| key.into_owned()).collect();
+    if !keys.is_empty() {
+        keys.sort();
+        keys.dedup();
+        pattern.push('?');
+        pattern.push_str(&keys.join("&"));
+    }
+
+    Some(pattern)
+}
+
+/// Whether a URL looks like it serves a binary file and is worth probing first
+pub fn is_suspect(url: &str) -> bool {
+    let Ok(parsed) = Url::parse(url) else {
+        return false;
+    };
+    let Some(segments) = parsed.path_segments() else {
+        return false;
+    };
+
+    let segments: Vec<String> = segments.map(|segment| segment.to_ascii_lowercase()).collect();
+    let binary_extension = segments
+        .last()
+        .and_then(|last| extension(last))
+        .is_some_and(|ext| BINARY_EXTENSIONS.contains(&ext.as_str()));
+
+    binary_extension || segments.iter().any(|segment| DOWNLOAD_SEGMENTS.contains(&segment.as_str()))
+}
+
+fn extension(segment: &str) -> Option<String> {
+    let (stem, ext) = segment.rsplit_once('.')?;
+    let is_extension = !stem.is_empty() && (1..=5).contains(&ext.len()) && ext.chars().all(|c| c.is_ascii_alphanumeric());
+    is_extension.then(|| ext.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaf_segments_share_a_pattern() {
+        assert_eq!(path_pattern("https://en.wikipedia.org/wiki/Rust").as_deref(), Some("en.wikipedia.org/wiki/*"));
+        assert_eq!(path_pattern("https://en.wikipedia.org/wiki/Go").as_deref(), Some("en.wikipedia.org/wiki/*"));
+        assert_eq!(
+            path_pattern("https://a.org/files/123/report.PDF?id=5&v=2").as_deref(),
+            Some("a.org/files/*/*.pdf?id&v")
+        );
+        assert_eq!(path_pattern("https://a.org/blog/").as_deref(), Some("a.org/blog/"));
+    }
+
+    #[test]
+    fn verdict_settles_after_enough_observations() {
+        let cache = ContentTypeCache::new();
+        for title in ["A", "B"] {
+            cache.record(&format!("https://a.org/files/{}.zip", title), "application/zip", false);
+        }
+        assert_eq!(cache.verdict("https://a.org/files/C.zip"), Verdict::Unknown);
+
+        cache.record("https://a.org/files/D.zip", "application/zip", false);
+        assert_eq!(cache.verdict("https://a.org/files/E.zip"), Verdict::Skip("application/zip".to_string()));
+
+        for title in ["A", "B", "C"] {
+            cache.record(&format!("https://a.org/wiki/{}", title), "text/html", true);
+        }
+        assert_eq!(cache.verdict("https://a.org/wiki/Other"), Verdict::Fetch);
+    }
+}
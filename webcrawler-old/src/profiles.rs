@@ -0,0 +1,156 @@
+use crate::config::{ProfileAuth, RequestProfile};
+use chrono::{DateTime, Utc};
+use reqwest::header::{ACCEPT_LANGUAGE, COOKIE, SET_COOKIE, USER_AGENT};
+use reqwest::{RequestBuilder, Response};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+/// Request profiles matched by host, with the cookie jars of the profiles that keep one
+/// Jars are kept per profile, so a profile's subdomains share its cookies
+pub struct RequestProfiles {
+    profiles: Vec<RequestProfile>,
+    jars: Mutex<HashMap<String, BTreeMap<String, String>>>,
+}
+
+impl RequestProfiles {
+    pub fn new(profiles: &[RequestProfile]) -> Self {
+        let profiles: Vec<RequestProfile> = profiles
+            .iter()
+            .cloned()
+            .map(|mut profile| {
+                profile.host = profile.host.trim().trim_start_matches('.').to_ascii_lowercase();
+                profile
+            })
+            .collect();
+
+        let mut jars: HashMap<String, BTreeMap<String, String>> = HashMap::new();
+        for profile in &profiles {
+            let jar = jars.entry(profile.host.clone()).or_default();
+            jar.extend(profile.cookies.iter().filter_map(|cookie| cookie_pair(cookie)));
+        }
+
+        Self {
+            profiles,
+            jars: Mutex::new(jars),
+        }
+    }
+
+    /// Add the matching profile's headers, credentials and cookies to a request
+    pub fn apply(&self, host: &str, request: RequestBuilder) -> RequestBuilder {
+        let Some(profile) = self.profile_for(host) else {
+            return request;
+        };
+
+        let mut request = request;
+        for (name, value) in &profile.headers {
+            request = request.header(name, value);
+        }
+        if let Some(user_agent) = &profile.user_agent {
+            request = request.header(USER_AGENT, user_agent);
+        }
+        if let Some(accept_language) = &profile.accept_language {
+            request = request.header(ACCEPT_LANGUAGE, accept_language);
+        }
+        request = match &profile.auth {
+            Some(ProfileAuth::Basic { username, password }) => request.basic_auth(username, password.as_ref()),
+            Some(ProfileAuth::Bearer { token }) => request.bearer_auth(token),
+            None => request,
+        };
+
+        let jars = self.jars.lock().unwrap();
+        if let Some(jar) = jars.get(&profile.host).filter(|jar| !jar.is_empty()) {
+            request = request.header(COOKIE, cookie_header(jar));
+        }
+        request
+    }
+
+    /// Keep the cookies a response sets, if the profile of the host that sent it has a cookie jar
+    pub fn store_cookies(&self, response: &Response) {
+        let host = response.url().host_str().unwrap_or_default();
+        let Some(profile) = self.profile_for(host).filter(|profile| profile.cookie_jar) else {
+            return;
+        };
+
+        let mut jars = self.jars.lock().unwrap();
+        let jar = jars.entry(profile.host.clone()).or_default();
+        for set_cookie in response.headers().get_all(SET_COOKIE) {
+            if let Ok(set_cookie) = set_cookie.to_str() {
+                apply_set_cookie(jar, set_cookie);
+            }
+        }
+    }
+
+    /// Jars of profiles that persist their cookies, as `Cookie` header values by profile host
+    pub fn persistent_cookies(&self) -> Vec<(String, String)> {
+        let jars = self.jars.lock().unwrap();
+        self.profiles
+            .iter()
+            .filter(|profile| profile.cookie_jar && profile.persist_cookies)
+            .filter_map(|profile| Some((profile.host.clone(), cookie_header(jars.get(&profile.host)?))))
+            .collect()
+    }
+
+    /// Restore a saved jar, if its profile still persists cookies
+    /// Saved cookies take precedence over the profile's initial ones
+    pub fn restore_cookies(&self, host: &str, cookies: &str) {
+        let persists = self
+            .profiles
+            .iter()
+            .any(|profile| profile.host == host && profile.cookie_jar && profile.persist_cookies);
+        if !persists {
+            return;
+        }
+
+        let mut jars = self.jars.lock().unwrap();
+        jars.entry(host.to_string())
+            .or_default()
+            .extend(cookies.split(';').filter_map(cookie_pair));
+    }
+
+    /// The profile for the host itself, or else for its closest parent domain
+    fn profile_for(&self, host: &str) -> Option<&RequestProfile> {
+        let host = host.to_ascii_lowercase();
+        self.profiles
+            .iter()
+            .filter(|profile| {
+                host == profile.host
+                    || host.strip_suffix(profile.host.as_str()).is_some_and(|prefix| prefix.ends_with('.'))
+            })
+            .max_by_key(|profile| profile.host.len())
+    }
+}
+
+/// Update a jar from a `Set-Cookie` value, dropping cookies that are already expired
+fn apply_set_cookie(jar: &mut BTreeMap<String, String>, set_cookie: &str) {
+    let mut parts = set_cookie.split(';');
+    let Some((name, value)) = parts.next().and_then(cookie_pair) else {
+        return;
+    };
+
+    let expired = parts.any(|attribute| {
+        let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        let (key, value) = (key.trim(), value.trim());
+        (key.eq_ignore_ascii_case("max-age") && value.parse::<i64>().is_ok_and(|age| age <= 0))
+            || (key.eq_ignore_ascii_case("expires")
+                && DateTime::parse_from_rfc2822(value).is_ok_and(|expires| expires < Utc::now()))
+    });
+
+    if expired {
+        jar.remove(&name);
+    } else {
+        jar.insert(name, value);
+    }
+}
+
+fn cookie_pair(pair: &str) -> Option<(String, String)> {
+    let (name, value) = pair.split_once('=')?;
+    let name = name.trim();
+    (!name.is_empty()).then(|| (name.to_string(), value.trim().to_string()))
+}
+
+fn cookie_header(jar: &BTreeMap<String, String>) -> String {
+    jar.iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
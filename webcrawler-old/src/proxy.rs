@@ -0,0 +1,314 @@
+use reqwest::{Client, ClientBuilder, Proxy, Response, StatusCode};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use url::Url;
+
+/// Consecutive request failures after which a proxy is evicted until it passes a health check
+const MAX_CONSECUTIVE_FAILURES: usize = 3;
+const HEALTH_CHECK_INTERVAL_SECS: u64 = 60;
+const HEALTH_CHECK_TIMEOUT_SECS: u64 = 10;
+
+struct PooledProxy {
+    label: String,
+    client: Client,
+    healthy: AtomicBool,
+    requests: AtomicUsize,
+    failures: AtomicUsize,
+    consecutive_failures: AtomicUsize,
+    total_latency_ms: AtomicU64,
+}
+
+/// Point-in-time view of one proxy for the UI
+#[derive(Debug, Clone)]
+pub struct ProxySnapshot {
+    pub label: String,
+    pub healthy: bool,
+    pub requests: usize,
+    pub failures: usize,
+    pub avg_latency_ms: u64,
+    pub hosts: usize,
+}
+
+/// Pool of HTTP/SOCKS5 proxies; each host sticks to one healthy proxy
+pub struct ProxyPool {
+    proxies: Vec<PooledProxy>,
+    assignments: Mutex<HashMap<String, usize>>,
+    check_url: String,
+}
+
+impl ProxyPool {
+    /// Build one client per proxy URL from a shared client configuration
+    pub fn new(
+        proxy_urls: &[String],
+        check_url: &str,
+        builder: impl Fn() -> ClientBuilder,
+    ) -> Result<Self, reqwest::Error> {
+        let proxies = proxy_urls
+            .iter()
+            .map(|proxy_url| {
+                let client = builder().proxy(Proxy::all(proxy_url)?).build()?;
+                Ok(PooledProxy {
+                    label: proxy_label(proxy_url),
+                    client,
+                    healthy: AtomicBool::new(true),
+                    requests: AtomicUsize::new(0),
+                    failures: AtomicUsize::new(0),
+                    consecutive_failures: AtomicUsize::new(0),
+                    total_latency_ms: AtomicU64::new(0),
+                })
+            })
+            .collect::<Result<Vec<_>, reqwest::Error>>()?;
+
+        Ok(Self {
+            proxies,
+            assignments: Mutex::new(HashMap::new()),
+            check_url: check_url.to_string(),
+        })
+    }
+
+    /// Pick the proxy for a URL's host, reassigning the host if its proxy was evicted
+    /// Returns None for URLs without a host
+    pub fn route(&self, url: &str) -> Option<(usize, Client)> {
+        if self.proxies.is_empty() {
+            return None;
+        }
+        let host = Url::parse(url).ok()?.host_str()?.to_string();
+
+        let mut assignments = self.assignments.lock().unwrap();
+        if let Some(&index) = assignments.get(&host)
+            && self.proxies[index].healthy.load(Ordering::Relaxed) {
+            return Some((index, self.proxies[index].client.clone()));
+        }
+
+        let index = self.least_loaded(&assignments);
+        assignments.insert(host, index);
+        Some((index, self.proxies[index].client.clone()))
+    }
+
+    /// Record the outcome of a request sent through a proxy
+    fn record(&self, index: usize, success: bool, latency: Duration) {
+        let proxy = &self.proxies[index];
+        proxy.requests.fetch_add(1, Ordering::Relaxed);
+        proxy.total_latency_ms.fetch_add(latency.as_millis() as u64, Ordering::Relaxed);
+
+        if success {
+            proxy.consecutive_failures.store(0, Ordering::Relaxed);
+            return;
+        }
+
+        proxy.failures.fetch_add(1, Ordering::Relaxed);
+        let consecutive = proxy.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if consecutive >= MAX_CONSECUTIVE_FAILURES {
+            proxy.healthy.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Record the result of a request sent through a proxy
+    /// A 407 means the proxy rejected our credentials, so it counts against the proxy rather than the site
+    pub fn record_result(&self, index: usize, result: &Result<Response, reqwest::Error>, latency: Duration) {
+        let success = result
+            .as_ref()
+            .is_ok_and(|response| response.status() != StatusCode::PROXY_AUTHENTICATION_REQUIRED);
+        self.record(index, success, latency);
+    }
+
+    /// Periodically fetch the check URL through every proxy, readmitting those that respond
+    pub async fn run_health_checks(self: Arc<Self>) {
+        loop {
+            tokio::time::sleep(Duration::from_secs(HEALTH_CHECK_INTERVAL_SECS)).await;
+            self.check_health().await;
+        }
+    }
+
+    /// Fetch the check URL once through every proxy, marking each healthy or evicted
+    async fn check_health(&self) {
+        for proxy in &self.proxies {
+            let response = proxy
+                .client
+                .get(&self.check_url)
+                .timeout(Duration::from_secs(HEALTH_CHECK_TIMEOUT_SECS))
+                .send()
+                .await;
+            let healthy = response.is_ok_and(|r| r.status().is_success());
+
+            proxy.healthy.store(healthy, Ordering::Relaxed);
+            if healthy {
+                proxy.consecutive_failures.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<ProxySnapshot> {
+        let assignments = self.assignments.lock().unwrap();
+        self.proxies
+            .iter()
+            .enumerate()
+            .map(|(index, proxy)| {
+                let requests = proxy.requests.load(Ordering::Relaxed);
+                ProxySnapshot {
+                    label: proxy.label.clone(),
+                    healthy: proxy.healthy.load(Ordering::Relaxed),
+                    requests,
+                    failures: proxy.failures.load(Ordering::Relaxed),
+                    avg_latency_ms: proxy.total_latency_ms.load(Ordering::Relaxed) / requests.max(1) as u64,
+                    hosts: assignments.values().filter(|&&assigned| assigned == index).count(),
+                }
+            })
+            .collect()
+    }
+
+    /// Healthy proxy with the fewest hosts; if all are evicted, the one closest to recovering
+    fn least_loaded(&self, assignments: &HashMap<String, usize>) -> usize {
+        let mut loads = vec![0usize; self.proxies.len()];
+        for &index in assignments.values() {
+            loads[index] += 1;
+        }
+
+        let healthy = (0..self.proxies.len())
+            .filter(|&index| self.proxies[index].healthy.load(Ordering::Relaxed))
+            .min_by_key(|&index| loads[index]);
+
+        healthy.unwrap_or_else(|| {
+            (0..self.proxies.len())
+                .min_by_key(|&index| self.proxies[index].consecutive_failures.load(Ordering::Relaxed))
+                .unwrap_or(0)
+        })
+    }
+}
+
+/// Display name for a proxy without its credentials
+fn proxy_label(proxy_url: &str) -> String {
+    let Ok(parsed) = Url::parse(proxy_url) else {
+        return proxy_url.to_string();
+    };
+    match parsed.port() {
+        Some(port) => format!("{}://{}:{}", parsed.scheme(), parsed.host_str().unwrap_or(""), port),
+        None => format!("{}://{}", parsed.scheme(), parsed.host_str().unwrap_or("")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU16;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// A local proxy that answers every request, CONNECT included, with a settable status
+    struct StubProxy {
+        url: String,
+        status: Arc<AtomicU16>,
+        requests: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl StubProxy {
+        async fn start(status: u16) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let status = Arc::new(AtomicU16::new(status));
+            let requests = Arc::new(Mutex::new(Vec::new()));
+
+            let (served_status, served_requests) = (status.clone(), requests.clone());
+            tokio::spawn(async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    let mut head = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => break,
+                            Ok(n) => head.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let head = String::from_utf8_lossy(&head);
+                    served_requests.lock().unwrap().push(head.lines().next().unwrap_or_default().to_string());
+
+                    let response = format!(
+                        "HTTP/1.1 {} Stub\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        served_status.load(Ordering::Relaxed)
+                    );
+                    let _ = stream.write_all(response.as_bytes()).await;
+                }
+            });
+
+            Self { url, status, requests }
+        }
+
+        fn request_lines(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    fn pool(stubs: &[&StubProxy]) -> ProxyPool {
+        let urls: Vec<String> = stubs.iter().map(|stub| stub.url.clone()).collect();
+        ProxyPool::new(&urls, "http://check.test/", || Client::builder().timeout(Duration::from_secs(5))).unwrap()
+    }
+
+    /// Send a request through the proxy routed for `url` and record its outcome
+    async fn fetch(pool: &ProxyPool, url: &str) -> usize {
+        let (index, client) = pool.route(url).unwrap();
+        let result = client.get(url).send().await;
+        pool.record_result(index, &result, Duration::ZERO);
+        index
+    }
+
+    #[tokio::test]
+    async fn hosts_stick_to_their_proxy() {
+        let (first, second) = (StubProxy::start(200).await, StubProxy::start(200).await);
+        let pool = pool(&[&first, &second]);
+
+        let a = fetch(&pool, "http://a.test/1").await;
+        let b = fetch(&pool, "http://b.test/1").await;
+        assert_ne!(a, b, "new hosts go to the least loaded proxy");
+        assert_eq!(fetch(&pool, "http://a.test/2").await, a);
+        assert_eq!(fetch(&pool, "http://b.test/2").await, b);
+
+        let (a_stub, b_stub) = if a == 0 { (&first, &second) } else { (&second, &first) };
+        assert_eq!(a_stub.request_lines(), ["GET http://a.test/1 HTTP/1.1", "GET http://a.test/2 HTTP/1.1"]);
+        assert_eq!(b_stub.request_lines(), ["GET http://b.test/1 HTTP/1.1", "GET http://b.test/2 HTTP/1.1"]);
+    }
+
+    #[tokio::test]
+    async fn failing_proxy_is_evicted_and_readmitted_by_health_check() {
+        let (first, second) = (StubProxy::start(200).await, StubProxy::start(200).await);
+        let pool = pool(&[&first, &second]);
+
+        let index = fetch(&pool, "http://a.test/").await;
+        let (failing, other) = if index == 0 { (&first, 1) } else { (&second, 0) };
+        failing.status.store(502, Ordering::Relaxed);
+        // Only proxy failures count; a 502 from the site through the proxy is a successful proxy request
+        for _ in 0..MAX_CONSECUTIVE_FAILURES {
+            assert_eq!(fetch(&pool, "http://a.test/").await, index);
+        }
+        assert!(pool.snapshot()[index].healthy);
+
+        failing.status.store(407, Ordering::Relaxed);
+        for _ in 0..MAX_CONSECUTIVE_FAILURES {
+            fetch(&pool, "http://a.test/").await;
+        }
+        assert!(!pool.snapshot()[index].healthy);
+        assert_eq!(fetch(&pool, "http://a.test/").await, other, "the host moves to a healthy proxy");
+
+        failing.status.store(200, Ordering::Relaxed);
+        pool.check_health().await;
+        assert!(pool.snapshot()[index].healthy);
+        assert!(failing.request_lines().contains(&"GET http://check.test/ HTTP/1.1".to_string()));
+        assert_eq!(fetch(&pool, "http://c.test/").await, index, "a readmitted proxy takes new hosts again");
+    }
+
+    #[tokio::test]
+    async fn proxy_authentication_required_counts_as_failure() {
+        let stub = StubProxy::start(407).await;
+        let pool = pool(&[&stub]);
+
+        // Plain HTTP gets the 407 as a response, HTTPS as a failed CONNECT
+        fetch(&pool, "http://a.test/").await;
+        fetch(&pool, "https://a.test/").await;
+
+        let snapshot = &pool.snapshot()[0];
+        assert_eq!(snapshot.requests, 2);
+        assert_eq!(snapshot.failures, 2);
+        assert!(stub.request_lines().iter().any(|line| line.starts_with("CONNECT a.test:443")));
+    }
+}
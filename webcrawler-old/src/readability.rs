@@ -0,0 +1,324 @@
+use crate::normalize::{collapse_whitespace, decode_entities};
+use crate::parser::{Block, BlockKind};
+
+/// Elements whose text is never rendered, or is hidden from readers by the page
+pub const HIDDEN_SELECTOR: &str =
+    "head, title, script, style, noscript, template, svg, math, iframe, object, canvas, select, [hidden], \
+    [aria-hidden=true], [style*=\"display:none\"], [style*=\"display: none\"]";
+
+/// Navigation, page chrome and other text that is not part of the main content, by landmark and by
+/// whole class token or id; substrings would match state classes such as Wikipedia's
+/// `vector-feature-main-menu-pinned-disabled`, and classes of `<html>` and `<body>` never count
+pub const BOILERPLATE_SELECTOR: &str = "nav, aside, \
+    [role=navigation], [role=banner], [role=contentinfo], [role=complementary], [role=search], \
+    [role=dialog], [role=alertdialog], \
+    body [class~=nav], body [class~=navbar], body [class~=navigation], body [class~=menu], \
+    body [class~=footer], body [class~=sidebar], body [class~=comments], body [class~=share], \
+    body [class~=social], body [class~=related], body [class~=breadcrumb], body [class~=breadcrumbs], \
+    body [class~=cookie-banner], body [class~=cookie-notice], body [class~=cookie-consent], \
+    body [class~=advert], body [class~=advertisement], \
+    body [id=nav], body [id=navigation], body [id=menu], body [id=footer], body [id=sidebar], body [id=comments]";
+
+/// Headers and footers, which are page chrome unless they belong to a sectioning element
+pub const HEADER_FOOTER_SELECTOR: &str = "header, footer";
+/// Headers and footers of the content itself, such as a post's title and byline
+pub const SECTION_HEADER_FOOTER_SELECTOR: &str = "article header, article footer, section header, \
+    section footer, main header, main footer, [role=main] header, [role=main] footer";
+
+/// Elements that explicitly hold the main content
+pub const MAIN_SELECTOR: &str = "main, article, [role=main], [itemprop=articleBody]";
+
+/// Elements that start a new block of text
+pub const BLOCK_SELECTOR: &str = "address, article, aside, blockquote, br, dd, div, dl, dt, \
+    figcaption, figure, footer, form, h1, h2, h3, h4, h5, h6, header, hr, li, main, nav, ol, p, \
+    pre, section, table, td, th, tr, ul";
+
+/// Blocks with more of their text inside links than this are treated as navigation
+const MAX_LINK_DENSITY: f64 = 0.33;
+/// Blocks shorter than this only count as content when they lead into a content block
+const MIN_CONTENT_WORDS: usize = 8;
+
+/// Where the text chunk being collected sits, set by the classifying handlers before it arrives
+#[derive(Debug, Default, Clone, Copy)]
+struct ChunkContext {
+    hidden: bool,
+    link: bool,
+    boilerplate: bool,
+    header_footer: bool,
+    section_header_footer: bool,
+    main: bool,
+}
+
+/// A run of text between block-level element boundaries
+#[derive(Debug, Default)]
+pub struct TextBlock {
+    pub text: String,
+    /// Kind of the element that started the block, None for untyped containers like `<div>`
+    kind: Option<BlockKind>,
+    id: Option<String>,
+    /// Decoded text of a `<pre>` block with its line breaks and indentation intact
+    preformatted: Option<String>,
+    /// Source characters of the chunks, the base for the ratios below
+    raw_chars: usize,
+    link_chars: usize,
+    boilerplate_chars: usize,
+    main_chars: usize,
+}
+
+impl TextBlock {
+    fn link_density(&self) -> f64 {
+        self.link_chars as f64 / self.raw_chars.max(1) as f64
+    }
+
+    fn is_boilerplate(&self) -> bool {
+        self.boilerplate_chars * 2 > self.raw_chars
+    }
+
+    fn is_main(&self) -> bool {
+        self.main_chars * 2 > self.raw_chars
+    }
+
+    fn word_count(&self) -> usize {
+        self.text.split_whitespace().count()
+    }
+}
+
+/// Collects text chunks from lol_html into blocks for scoring
+/// Handlers for the hidden, link, boilerplate and main selectors must be registered before the
+/// collecting text handler, so each chunk's context is known when it is pushed
+#[derive(Debug, Default)]
+pub struct BlockCollector {
+    blocks: Vec<TextBlock>,
+    current: TextBlock,
+    context: ChunkContext,
+}
+
+impl BlockCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mark_hidden(&mut self) {
+        self.context.hidden = true;
+    }
+
+    pub fn mark_link(&mut self) {
+        self.context.link = true;
+    }
+
+    pub fn mark_boilerplate(&mut self) {
+        self.context.boilerplate = true;
+    }
+
+    pub fn mark_header_footer(&mut self) {
+        self.context.header_footer = true;
+    }
+
+    pub fn mark_section_header_footer(&mut self) {
+        self.context.section_header_footer = true;
+    }
+
+    pub fn mark_main(&mut self) {
+        self.context.main = true;
+    }
+
+    /// Add a text chunk to the current block and reset the chunk context
+    /// Returns whether the chunk is visible text
+    pub fn push_text(&mut self, text: &str) -> bool {
+        let context = std::mem::take(&mut self.context);
+        if context.hidden || text.is_empty() {
+            return false;
+        }
+
+        let block = &mut self.current;
+        block.text.push_str(text);
+        let chars = text.trim().len();
+        block.raw_chars += chars;
+        if context.link {
+            block.link_chars += chars;
+        }
+        if context.boilerplate || (context.header_footer && !context.section_header_footer) {
+            block.boilerplate_chars += chars;
+        }
+        if context.main {
+            block.main_chars += chars;
+        }
+        true
+    }
+
+    /// Close the current block and start one for a block-level element
+    pub fn start_block(&mut self, kind: Option<BlockKind>, id: Option<String>) {
+        self.end_block();
+        self.current.kind = kind;
+        self.current.id = id;
+    }
+
+    /// Close the current block at the end of its element; the text that follows starts an untyped block
+    pub fn end_element(&mut self) {
+        self.start_block(None, None);
+    }
+
+    /// Use an id found inside a heading when the heading itself has none
+    pub fn note_heading_id(&mut self, id: String) {
+        let block = &mut self.current;
+        if block.id.is_none() && block.kind.is_some_and(BlockKind::is_heading) {
+            block.id = Some(id);
+        }
+    }
+
+    /// Close the current block; its raw chunks are decoded only now, as an entity may span chunks
+    pub fn end_block(&mut self) {
+        let mut block = std::mem::take(&mut self.current);
+        let decoded = decode_entities(&block.text);
+        block.text = collapse_whitespace(&decoded);
+        if block.kind == Some(BlockKind::Pre) {
+            block.preformatted = Some(decoded.trim_matches(['\r', '\n']).to_string());
+        }
+        if !block.text.is_empty() {
+            self.blocks.push(block);
+        }
+    }
+
+    pub fn finish(mut self) -> Vec<TextBlock> {
+        self.end_block();
+        self.blocks
+    }
+}
+
+/// All visible text of the page
+pub fn full_text(blocks: &[TextBlock]) -> String {
+    blocks.iter().map(|block| block.text.as_str()).collect::<Vec<_>>().join(" ")
+}
+
+/// Text of the blocks that make up the main content
+/// Falls back to the full text when no block qualifies
+pub fn main_text(blocks: &[TextBlock]) -> String {
+    let selected: Vec<&str> = blocks
+        .iter()
+        .zip(main_content(blocks))
+        .filter(|(_, main)| *main)
+        .map(|(block, _)| block.text.as_str())
+        .collect();
+
+    if selected.is_empty() {
+        full_text(blocks)
+    } else {
+        selected.join(" ")
+    }
+}
+
+/// Typed blocks in document order, flagged with whether they are part of the main content
+pub fn outline(blocks: &[TextBlock]) -> Vec<Block> {
+    blocks
+        .iter()
+        .zip(main_content(blocks))
+        .filter_map(|(block, main)| {
+            Some(Block {
+                kind: block.kind?,
+                text: block.preformatted.clone().unwrap_or_else(|| block.text.clone()),
+                id: block.id.clone(),
+                main,
+            })
+        })
+        .collect()
+}
+
+/// Which blocks make up the main content, scored by length and link density
+/// Inside an explicit `<main>` or `<article>` only those blocks are considered
+fn main_content(blocks: &[TextBlock]) -> Vec<bool> {
+    let has_main = blocks.iter().any(|block| block.is_main());
+    let candidate = |block: &TextBlock| {
+        !block.is_boilerplate() && block.link_density() <= MAX_LINK_DENSITY && (!has_main || block.is_main())
+    };
+    let mut selected: Vec<bool> = blocks
+        .iter()
+        .map(|block| candidate(block) && block.word_count() >= MIN_CONTENT_WORDS)
+        .collect();
+
+    // Short blocks such as headings and captions are kept when they lead into content
+    for index in (0..blocks.len()).rev() {
+        let leads_into_content = selected.get(index + 1).copied().unwrap_or(false);
+        if !selected[index] && leads_into_content && candidate(&blocks[index]) && blocks[index].link_chars == 0 {
+            selected[index] = true;
+        }
+    }
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extractors::Extractors;
+    use crate::parser::{parse_html, BlockKind};
+
+    fn main_text(html: &str, url: &str) -> String {
+        parse_html(html.to_string(), url, &Extractors::with_defaults()).unwrap().main_text
+    }
+
+    #[test]
+    fn blocks_end_at_their_end_tag() {
+        let html = r#"<div class="mw-heading"><h2 id="History">History</h2><span class="mw-editsection">[<a href="/w/index.php?action=edit">edit</a>]</span></div>
+            <ul><li><p>First point</p> and its aside</li></ul>"#;
+        let parsed = parse_html(html.to_string(), "https://example.org/", &Extractors::new()).unwrap();
+        let blocks: Vec<(BlockKind, &str, Option<&str>)> = parsed
+            .blocks
+            .iter()
+            .map(|block| (block.kind, block.text.as_str(), block.id.as_deref()))
+            .collect();
+        assert_eq!(blocks, [(BlockKind::H2, "History", Some("History")), (BlockKind::P, "First point", None)]);
+        assert_eq!(parsed.full_text, "History [edit] First point and its aside");
+    }
+
+    #[test]
+    fn wikipedia_vector_2022() {
+        let html = include_str!("../tests/fixtures/readability/wikipedia.html");
+        assert_eq!(
+            main_text(html, "https://en.wikipedia.org/wiki/Rust_(programming_language)"),
+            "From Wikipedia, the free encyclopedia \
+             Rust is a general-purpose programming language emphasizing performance, type safety and concurrency. \
+             It enforces memory safety without a garbage collector.[1] \
+             Rust began as a personal project by Mozilla employee Graydon Hoare in 2006, \
+             and Mozilla sponsored the project from 2009 onwards."
+        );
+    }
+
+    #[test]
+    fn blog_post() {
+        let html = include_str!("../tests/fixtures/readability/blog.html");
+        assert_eq!(
+            main_text(html, "https://crumbandcrust.example/2026/03/02/baking-sourdough-at-home/"),
+            "Baking sourdough at home Posted on March 2, 2026 \
+             A sourdough starter is nothing more than flour and water left to ferment until wild yeast and bacteria take hold. \
+             Feeding the starter \
+             Feed the starter twice a day with equal weights of flour and water, and discard half of it before each feeding."
+        );
+    }
+
+    #[test]
+    fn news_article() {
+        let html = include_str!("../tests/fixtures/readability/news.html");
+        assert_eq!(
+            main_text(html, "https://courier.example/news/local/tram-line"),
+            "City council approves new tram line \
+             The eight kilometre route will link the harbour with the university by 2029. \
+             The first trams are expected to run in the spring of 2029. \
+             The city council voted on Tuesday evening to approve the long-debated tram line, \
+             ending almost a decade of planning disputes. \
+             Construction is due to begin next spring, \
+             and the council expects the first passengers to ride the line before the end of 2029."
+        );
+    }
+
+    #[test]
+    fn forum_topic() {
+        let html = include_str!("../tests/fixtures/readability/forum.html");
+        assert_eq!(
+            main_text(html, "https://forum.example/viewtopic.php?t=7"),
+            "by breadhead \u{bb} Sun Mar 01, 2026 9:12 am \
+             My starter rises nicely for a few hours after feeding but then collapses \
+             and smells strongly of acetone by the next morning. \
+             by crumbshot \u{bb} Sun Mar 01, 2026 11:40 am \
+             It is hungry. Feed it more often, or use a higher ratio of fresh flour \
+             so the yeast does not run out of food overnight."
+        );
+    }
+}
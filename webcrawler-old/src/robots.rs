@@ -0,0 +1,65 @@
+/// Robots token matched by `<meta name="...">` and user-agent scoped `X-Robots-Tag` values
+pub const USER_AGENT_TOKEN: &str = "webcrawler";
+
+pub const NOINDEX: &str = "noindex";
+pub const NOFOLLOW: &str = "nofollow";
+/// Link rel of user-generated content such as comments and forum posts
+pub const UGC: &str = "ugc";
+/// Link rel of paid or affiliate links
+pub const SPONSORED: &str = "sponsored";
+
+/// Link rel values that ask crawlers not to follow the link
+pub const NOFOLLOW_RELS: [&str; 3] = [NOFOLLOW, UGC, SPONSORED];
+
+/// Directives that carry their own `name: value`, so their colon is not a user agent prefix
+const VALUED_DIRECTIVES: [&str; 4] = ["unavailable_after", "max-snippet", "max-image-preview", "max-video-preview"];
+
+/// Whether a `<meta name>` addresses this crawler
+pub fn is_robots_meta(name: &str) -> bool {
+    let name = name.trim();
+    name.eq_ignore_ascii_case("robots") || name.eq_ignore_ascii_case(USER_AGENT_TOKEN)
+}
+
+/// Parse a comma-separated directive list, expanding `none` to `noindex, nofollow`
+pub fn parse_directives(value: &str) -> Vec<String> {
+    let mut directives = Vec::new();
+    for directive in value.split(',') {
+        let directive = directive.trim().to_ascii_lowercase();
+        match directive.as_str() {
+            "" => {}
+            "none" => {
+                directives.push(NOINDEX.to_string());
+                directives.push(NOFOLLOW.to_string());
+            }
+            _ => directives.push(directive),
+        }
+    }
+    directives
+}
+
+/// Parse an `X-Robots-Tag` value, keeping directives for all crawlers or for this one
+/// `otherbot: noindex` is ignored, `webcrawler: noindex` and `noindex` apply
+pub fn header_directives(value: &str) -> Vec<String> {
+    if let Some((prefix, rest)) = value.split_once(':') {
+        let prefix = prefix.trim().to_ascii_lowercase();
+        let is_user_agent = !VALUED_DIRECTIVES.iter().any(|directive| prefix.ends_with(directive));
+        if is_user_agent {
+            return if prefix == USER_AGENT_TOKEN {
+                parse_directives(rest)
+            } else {
+                Vec::new()
+            };
+        }
+    }
+    parse_directives(value)
+}
+
+/// Whether a space-separated rel attribute marks a link as not to be followed
+pub fn is_nofollow_rel(rel: &str) -> bool {
+    rel.split_ascii_whitespace()
+        .any(|value| NOFOLLOW_RELS.iter().any(|nofollow| value.eq_ignore_ascii_case(nofollow)))
+}
+
+pub fn has_directive(directives: &[String], directive: &str) -> bool {
+    directives.iter().any(|d| d == directive)
+}
@@ -0,0 +1,420 @@
+use crate::config::{CrawlerConfig, OutputCompression};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Suffix of a segment still being written; readers only ever see finished segments
+const PARTIAL_SUFFIX: &str = ".partial";
+const WRITE_BUFFER_SIZE: usize = 64 * 1024;
+const ZSTD_LEVEL: i32 = 3;
+/// Digits of the segment number in file names, so segments sort in order
+const INDEX_DIGITS: usize = 6;
+
+/// A finished segment as listed in the manifest
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SegmentEntry {
+    /// File name, relative to the manifest
+    pub file: String,
+    pub records: usize,
+    /// Size of the file, after compression
+    pub bytes: u64,
+    /// SHA-256 of the file, in hex
+    pub sha256: String,
+    /// RFC 3339
+    pub finished_at: String,
+}
+
+/// The finished segments of a stream, in the order they were written
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Manifest {
+    pub segments: Vec<SegmentEntry>,
+}
+
+impl Manifest {
+    /// Load a manifest, or an empty one if the stream has no finished segments yet
+    fn load(path: &Path) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replace the manifest through a temporary file, so it is never seen half-written
+    fn save(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let temp_path = with_suffix(path, ".tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(&json)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)
+    }
+}
+
+/// A file that hashes and counts the bytes written to it
+struct HashingFile {
+    file: File,
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl Write for HashingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+enum Encoder {
+    Plain(BufWriter<HashingFile>),
+    Gzip(GzEncoder<BufWriter<HashingFile>>),
+    Zstd(zstd::Encoder<'static, BufWriter<HashingFile>>),
+}
+
+impl Encoder {
+    fn new(file: File, compression: OutputCompression) -> io::Result<Self> {
+        let file = HashingFile {
+            file,
+            hasher: Sha256::new(),
+            bytes: 0,
+        };
+        let buffered = BufWriter::with_capacity(WRITE_BUFFER_SIZE, file);
+        Ok(match compression {
+            OutputCompression::None => Encoder::Plain(buffered),
+            OutputCompression::Gzip => Encoder::Gzip(GzEncoder::new(buffered, flate2::Compression::default())),
+            OutputCompression::Zstd => Encoder::Zstd(zstd::Encoder::new(buffered, ZSTD_LEVEL)?),
+        })
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Encoder::Plain(writer) => writer,
+            Encoder::Gzip(writer) => writer,
+            Encoder::Zstd(writer) => writer,
+        }
+    }
+
+    /// Write the end of the compressed stream and return the underlying file
+    fn finish(self) -> io::Result<HashingFile> {
+        let buffered = match self {
+            Encoder::Plain(writer) => writer,
+            Encoder::Gzip(writer) => writer.finish()?,
+            Encoder::Zstd(writer) => writer.finish()?,
+        };
+        buffered.into_inner().map_err(|e| e.into_error())
+    }
+}
+
+/// The segment being written
+struct Segment {
+    file_name: String,
+    encoder: Encoder,
+    records: usize,
+    /// Bytes written before compression
+    bytes: u64,
+    opened_at: Instant,
+}
+
+/// A JSONL stream written as numbered segments, e.g. `pages-000001.jsonl.zst`, next to a
+/// `pages.manifest.json` listing the finished ones
+/// A segment is written under a `.partial` name and only renamed into place and added to the manifest
+/// once it is complete: when it reaches the size or age limit, or when the stream is closed
+pub struct SegmentedFile {
+    dir: PathBuf,
+    name: String,
+    /// Extension of finished segments, with the compression's
+    extension: String,
+    compression: OutputCompression,
+    max_bytes: u64,
+    max_age: Duration,
+    manifest_path: PathBuf,
+    manifest: Manifest,
+    next_index: usize,
+    current: Option<Segment>,
+}
+
+impl SegmentedFile {
+    /// Open the stream whose unsegmented path would be `path`, e.g. `output/pages.jsonl`
+    /// Numbering continues after any segment already on disk, and segments an earlier run left out of
+    /// the manifest are added to it
+    pub fn open(path: &str, config: &CrawlerConfig) -> io::Result<Self> {
+        let path = Path::new(path);
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf();
+        let name = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("segment").to_string();
+        let mut extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("jsonl").to_string();
+        let segment_extensions = [".gz", ".zst", ""].map(|suffix| format!(".{}{}", extension, suffix));
+        match config.output_compression {
+            OutputCompression::None => {}
+            OutputCompression::Gzip => extension.push_str(".gz"),
+            OutputCompression::Zstd => extension.push_str(".zst"),
+        }
+
+        let manifest_path = dir.join(format!("{}.manifest.json", name));
+        let mut manifest = Manifest::load(&manifest_path)?;
+
+        let prefix = format!("{}-", name);
+        let mut next_index = 1;
+        // Segment names missing from the manifest, with whether they are still partial
+        let mut unlisted = Vec::new();
+        for entry in fs::read_dir(&dir)?.flatten() {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            let Some(index) = file_name
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.get(..INDEX_DIGITS))
+                .and_then(|digits| digits.parse::<usize>().ok())
+            else {
+                continue;
+            };
+            next_index = next_index.max(index + 1);
+
+            let (segment_name, partial) = match file_name.strip_suffix(PARTIAL_SUFFIX) {
+                Some(segment_name) => (segment_name.to_string(), true),
+                None => (file_name, false),
+            };
+            let is_segment = segment_extensions.iter().any(|extension| segment_name.ends_with(extension.as_str()));
+            if is_segment && !manifest.segments.iter().any(|listed| listed.file == segment_name) {
+                unlisted.push((segment_name, partial));
+            }
+        }
+
+        // A finished segment is unlisted when a run died between renaming it and saving the manifest
+        // A partial one was cut short: plain ones are kept up to their last complete line, a compressed
+        // one has no stream end to recover from
+        unlisted.sort();
+        let listed = manifest.segments.len();
+        for (segment_name, partial) in unlisted {
+            let entry = if !partial {
+                Some(finished_entry(&dir, segment_name)?)
+            } else if segment_name.ends_with(".gz") || segment_name.ends_with(".zst") {
+                eprintln!("Unfinished output segment {}{} from an earlier run is not in the manifest", segment_name, PARTIAL_SUFFIX);
+                None
+            } else {
+                recover_partial(&dir, segment_name)?
+            };
+            manifest.segments.extend(entry);
+        }
+        if manifest.segments.len() > listed {
+            manifest.save(&manifest_path)?;
+        }
+
+        Ok(Self {
+            dir,
+            name,
+            extension,
+            compression: config.output_compression,
+            max_bytes: config.segment_max_bytes,
+            max_age: Duration::from_secs(config.segment_max_secs),
+            manifest_path,
+            manifest,
+            next_index,
+            current: None,
+        })
+    }
+
+    /// Append a line to the current segment, starting one if needed
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.current.is_none() {
+            self.current = Some(self.start_segment()?);
+        }
+        if let Some(segment) = &mut self.current {
+            let writer = segment.encoder.writer();
+            writer.write_all(line.as_bytes())?;
+            writer.write_all(b"\n")?;
+            segment.records += 1;
+            segment.bytes += line.len() as u64 + 1;
+        }
+        Ok(())
+    }
+
+    /// Finish the current segment if it reached the size or age limit
+    pub fn rotate_if_due(&mut self) -> io::Result<()> {
+        let due = self
+            .current
+            .as_ref()
+            .is_some_and(|segment| segment.bytes >= self.max_bytes || segment.opened_at.elapsed() >= self.max_age);
+        if due {
+            self.finish_segment()?;
+        }
+        Ok(())
+    }
+
+    /// Finish the current segment, if any
+    pub fn close(&mut self) -> io::Result<()> {
+        self.finish_segment()
+    }
+
+    fn start_segment(&mut self) -> io::Result<Segment> {
+        let file_name = format!("{}-{:0width$}.{}", self.name, self.next_index, self.extension, width = INDEX_DIGITS);
+        self.next_index += 1;
+        let file = File::create(with_suffix(&self.dir.join(&file_name), PARTIAL_SUFFIX))?;
+        Ok(Segment {
+            file_name,
+            encoder: Encoder::new(file, self.compression)?,
+            records: 0,
+            bytes: 0,
+            opened_at: Instant::now(),
+        })
+    }
+
+    /// Complete the segment on disk, move it to its final name, then list it in the manifest
+    fn finish_segment(&mut self) -> io::Result<()> {
+        let Some(segment) = self.current.take() else {
+            return Ok(());
+        };
+        let hashing_file = segment.encoder.finish()?;
+        hashing_file.file.sync_all()?;
+
+        let final_path = self.dir.join(&segment.file_name);
+        fs::rename(with_suffix(&final_path, PARTIAL_SUFFIX), &final_path)?;
+
+        self.manifest.segments.push(SegmentEntry {
+            file: segment.file_name,
+            records: segment.records,
+            bytes: hashing_file.bytes,
+            sha256: format!("{:x}", hashing_file.hasher.finalize()),
+            finished_at: chrono::Utc::now().to_rfc3339(),
+        });
+        self.manifest.save(&self.manifest_path)
+    }
+}
+
+/// Finish a plain segment left under its `.partial` name, dropping a trailing incomplete line
+/// None when it has no complete line, in which case it is removed
+fn recover_partial(dir: &Path, file_name: String) -> io::Result<Option<SegmentEntry>> {
+    let final_path = dir.join(&file_name);
+    let partial_path = with_suffix(&final_path, PARTIAL_SUFFIX);
+    let bytes = fs::read(&partial_path)?;
+    let complete = bytes.iter().rposition(|&byte| byte == b'\n').map_or(0, |end| end + 1);
+    if complete == 0 {
+        fs::remove_file(&partial_path)?;
+        return Ok(None);
+    }
+
+    let file = File::options().write(true).open(&partial_path)?;
+    file.set_len(complete as u64)?;
+    file.sync_all()?;
+    fs::rename(&partial_path, &final_path)?;
+    finished_entry(dir, file_name).map(Some)
+}
+
+/// Manifest entry for a finished segment found on disk, hashing it and counting its records
+fn finished_entry(dir: &Path, file_name: String) -> io::Result<SegmentEntry> {
+    let path = dir.join(&file_name);
+    let mut hasher = Sha256::new();
+    let bytes = io::copy(&mut File::open(&path)?, &mut hasher)?;
+
+    let file = BufReader::new(File::open(&path)?);
+    let mut decoded: Box<dyn Read> = if file_name.ends_with(".gz") {
+        Box::new(GzDecoder::new(file))
+    } else if file_name.ends_with(".zst") {
+        Box::new(zstd::Decoder::with_buffer(file)?)
+    } else {
+        Box::new(file)
+    };
+    let mut lines = LineCounter(0);
+    io::copy(&mut decoded, &mut lines)?;
+
+    Ok(SegmentEntry {
+        file: file_name,
+        records: lines.0,
+        bytes,
+        sha256: format!("{:x}", hasher.finalize()),
+        finished_at: chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+/// A sink that counts the lines written to it
+struct LineCounter(usize);
+
+impl Write for LineCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.iter().filter(|&&byte| byte == b'\n').count();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_recovers_plain_partial_segments() {
+        let dir = std::env::temp_dir().join(format!("segments-recover-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("pages-000001.jsonl.partial"), "{\"a\":1}\n{\"a\":2}\n{\"a\"").unwrap();
+        fs::write(dir.join("pages-000002.jsonl.partial"), "{\"a\"").unwrap();
+        fs::write(dir.join("pages-000003.jsonl.zst.partial"), "truncated").unwrap();
+
+        let mut segments = SegmentedFile::open(dir.join("pages.jsonl").to_str().unwrap(), &CrawlerConfig::default()).unwrap();
+        let recovered = fs::read(dir.join("pages-000001.jsonl")).unwrap();
+        assert_eq!(recovered, b"{\"a\":1}\n{\"a\":2}\n");
+        assert!(!dir.join("pages-000002.jsonl.partial").exists());
+        assert!(dir.join("pages-000003.jsonl.zst.partial").exists());
+
+        let manifest = Manifest::load(&dir.join("pages.manifest.json")).unwrap();
+        assert_eq!(manifest.segments.len(), 1);
+        let entry = &manifest.segments[0];
+        assert_eq!(entry.file, "pages-000001.jsonl");
+        assert_eq!(entry.records, 2);
+        assert_eq!(entry.bytes, recovered.len() as u64);
+        assert_eq!(entry.sha256, format!("{:x}", Sha256::digest(&recovered)));
+
+        // New segments are numbered after every segment on disk
+        segments.write_line("{\"a\":3}").unwrap();
+        segments.close().unwrap();
+        assert!(dir.join("pages-000004.jsonl").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn open_lists_finished_segments_missing_from_the_manifest() {
+        let dir = std::env::temp_dir().join(format!("segments-unlisted-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pages.jsonl");
+        let config = CrawlerConfig {
+            output_compression: OutputCompression::Gzip,
+            ..CrawlerConfig::default()
+        };
+
+        let mut segments = SegmentedFile::open(path.to_str().unwrap(), &config).unwrap();
+        for line in ["{\"a\":1}", "{\"a\":2}", "{\"a\":3}"] {
+            segments.write_line(line).unwrap();
+        }
+        segments.close().unwrap();
+        let written = segments.manifest.segments[0].clone();
+
+        // As if the run died after the rename, before the manifest was saved
+        fs::remove_file(dir.join("pages.manifest.json")).unwrap();
+        SegmentedFile::open(path.to_str().unwrap(), &config).unwrap();
+
+        let manifest = Manifest::load(&dir.join("pages.manifest.json")).unwrap();
+        assert_eq!(manifest.segments.len(), 1);
+        let entry = &manifest.segments[0];
+        assert_eq!(entry.file, "pages-000001.jsonl.gz");
+        assert_eq!((entry.records, entry.bytes, &entry.sha256), (written.records, written.bytes, &written.sha256));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,360 @@
+use crate::http_client::HttpClient;
+use crate::parser;
+use crate::rate_limiter::RateLimiter;
+use crate::url_store::{DEFAULT_PRIORITY, UrlStore};
+use chrono::{DateTime, NaiveDate};
+use flate2::read::GzDecoder;
+use std::collections::HashSet;
+use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+const MAX_ROBOTS_SIZE: usize = 512 * 1024;
+const MAX_SITEMAP_SIZE: usize = 50 * 1024 * 1024;
+const MAX_SITEMAP_URLS: usize = 50_000;
+const MAX_INDEX_DEPTH: usize = 2;
+const SITEMAP_REFRESH_SECS: u64 = 24 * 60 * 60;
+/// Frontier priority per unit of `<priority>` above the default
+const SITEMAP_PRIORITY_SCALE: f32 = 100.0;
+const DEFAULT_SITEMAP_PRIORITY: f32 = 0.5;
+
+/// A single `<url>` or `<sitemap>` entry
+#[derive(Debug, Clone)]
+pub struct SitemapEntry {
+    pub loc: String,
+    pub lastmod: Option<u64>,
+    pub priority: Option<f32>,
+}
+
+/// Parsed sitemap document
+#[derive(Debug)]
+pub enum Sitemap {
+    /// `<sitemapindex>` pointing at further sitemaps
+    Index(Vec<SitemapEntry>),
+    /// `<urlset>` or plain text list of page URLs
+    UrlSet(Vec<SitemapEntry>),
+}
+
+/// Extract `Sitemap:` lines from a robots.txt body
+pub fn robots_sitemaps(robots_txt: &str) -> Vec<String> {
+    robots_txt
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            if !key.trim().eq_ignore_ascii_case("sitemap") {
+                return None;
+            }
+            let value = value.split('#').next().unwrap_or("").trim();
+            (!value.is_empty()).then(|| value.to_string())
+        })
+        .collect()
+}
+
+/// Decompress a body if it starts with the gzip magic bytes
+pub fn decompress_if_gzipped(body: Vec<u8>) -> std::io::Result<Vec<u8>> {
+    if !body.starts_with(&[0x1f, 0x8b]) {
+        return Ok(body);
+    }
+
+    let mut decompressed = Vec::new();
+    GzDecoder::new(body.as_slice())
+        .take(MAX_SITEMAP_SIZE as u64 + 1)
+        .read_to_end(&mut decompressed)?;
+    if decompressed.len() > MAX_SITEMAP_SIZE {
+        return Err(std::io::Error::other("decompressed sitemap too large"));
+    }
+    Ok(decompressed)
+}
+
+/// Parse a sitemap index, urlset, or plain text sitemap
+pub fn parse_sitemap(body: &[u8]) -> Option<Sitemap> {
+    let text = String::from_utf8_lossy(body);
+    let trimmed = text.trim_start_matches('\u{feff}').trim_start();
+
+    // Plain text sitemaps list one URL per line
+    if !trimmed.starts_with('<') {
+        let entries = trimmed
+            .lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with("http://") || line.starts_with("https://"))
+            .take(MAX_SITEMAP_URLS)
+            .map(|line| SitemapEntry {
+                loc: line.to_string(),
+                lastmod: None,
+                priority: None,
+            })
+            .collect();
+        return Some(Sitemap::UrlSet(entries));
+    }
+
+    let doc = roxmltree::Document::parse(trimmed).ok()?;
+    let root = doc.root_element();
+    let (entry_tag, is_index) = match root.tag_name().name() {
+        "sitemapindex" => ("sitemap", true),
+        "urlset" => ("url", false),
+        _ => return None,
+    };
+
+    let entries = root
+        .children()
+        .filter(|node| node.tag_name().name() == entry_tag)
+        .filter_map(|node| {
+            let child_text = |name: &str| {
+                node.children()
+                    .find(|child| child.tag_name().name() == name)
+                    .and_then(|child| child.text())
+                    .map(str::trim)
+            };
+
+            let loc = child_text("loc").filter(|loc| !loc.is_empty())?;
+            Some(SitemapEntry {
+                loc: loc.to_string(),
+                lastmod: child_text("lastmod").and_then(parse_lastmod),
+                priority: child_text("priority")
+                    .and_then(|p| p.parse::<f32>().ok())
+                    .map(|p| p.clamp(0.0, 1.0)),
+            })
+        })
+        .take(MAX_SITEMAP_URLS)
+        .collect();
+
+    if is_index {
+        Some(Sitemap::Index(entries))
+    } else {
+        Some(Sitemap::UrlSet(entries))
+    }
+}
+
+/// Parse a W3C datetime (`2024-01-31` or RFC 3339) into unix seconds
+pub fn parse_lastmod(value: &str) -> Option<u64> {
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return u64::try_from(datetime.timestamp()).ok();
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+    u64::try_from(date.and_hms_opt(0, 0, 0)?.and_utc().timestamp()).ok()
+}
+
+/// Discovers sitemaps for each newly seen host and feeds their URLs into the frontier
+pub struct SitemapIngestor {
+    http_client: Arc<HttpClient>,
+    rate_limiter: RateLimiter,
+    url_store: UrlStore,
+    seen_hosts: Arc<Mutex<HashSet<String>>>,
+    urls_added: Arc<AtomicUsize>,
+}
+
+impl SitemapIngestor {
+    pub fn new(
+        http_client: Arc<HttpClient>,
+        rate_limiter: RateLimiter,
+        url_store: UrlStore,
+        urls_added: Arc<AtomicUsize>,
+    ) -> Self {
+        Self {
+            http_client,
+            rate_limiter,
+            url_store,
+            seen_hosts: Arc::new(Mutex::new(HashSet::new())),
+            urls_added,
+        }
+    }
+
+    /// Start sitemap discovery for the host of `url` the first time the host is seen
+    pub fn discover(&self, url: &str) {
+        let Ok(parsed) = Url::parse(url) else {
+            return;
+        };
+        let Some(host) = parsed.host_str() else {
+            return;
+        };
+
+        if !self.seen_hosts.lock().unwrap().insert(host.to_string()) {
+            return;
+        }
+
+        let ingestor = self.clone();
+        let origin = parsed.origin().ascii_serialization();
+        let host = host.to_string();
+        tokio::spawn(async move {
+            ingestor.ingest_origin(&origin, &host).await;
+        });
+    }
+
+    /// Find sitemaps via robots.txt, falling back to /sitemap.xml
+    /// robots.txt may declare sitemaps on other hosts, but they only feed in URLs of the declaring host
+    async fn ingest_origin(&self, origin: &str, host: &str) {
+        let robots_url = format!("{}/robots.txt", origin);
+        self.rate_limiter.wait_if_needed(&robots_url).await;
+
+        let mut sitemap_urls = match self.http_client.fetch_bytes(&robots_url, MAX_ROBOTS_SIZE).await {
+            Ok(body) => robots_sitemaps(&String::from_utf8_lossy(&body)),
+            Err(_) => Vec::new(),
+        };
+        if sitemap_urls.is_empty() {
+            sitemap_urls.push(format!("{}/sitemap.xml", origin));
+        }
+
+        for sitemap_url in sitemap_urls {
+            self.ingest_sitemap(sitemap_url, host, 0).await;
+        }
+    }
+
+    /// Fetch one sitemap, recursing into sitemap indexes
+    /// Only URLs on `scope_host` are added; an index may only point at sitemaps on its own host
+    async fn ingest_sitemap(&self, sitemap_url: String, scope_host: &str, depth: usize) {
+        if !self.url_store.sitemap_due(&sitemap_url, SITEMAP_REFRESH_SECS) {
+            return;
+        }
+
+        self.rate_limiter.wait_if_needed(&sitemap_url).await;
+        let Ok(body) = self.http_client.fetch_bytes(&sitemap_url, MAX_SITEMAP_SIZE).await else {
+            return;
+        };
+        self.url_store.mark_sitemap_fetched(&sitemap_url);
+
+        let Some(sitemap) = decompress_if_gzipped(body)
+            .ok()
+            .and_then(|body| parse_sitemap(&body)) else {
+            return;
+        };
+
+        match sitemap {
+            Sitemap::Index(children) => {
+                if depth >= MAX_INDEX_DEPTH {
+                    return;
+                }
+                let index_host = host_of(&sitemap_url);
+                for child in children {
+                    if host_of(&child.loc) != index_host {
+                        continue;
+                    }
+                    Box::pin(self.ingest_sitemap(child.loc, scope_host, depth + 1)).await;
+                }
+            }
+            Sitemap::UrlSet(entries) => {
+                for entry in entries {
+                    // Sitemaps may only list URLs of the host that declared them
+                    if host_of(&entry.loc).as_deref() != Some(scope_host) || !parser::is_crawlable(&entry.loc) {
+                        continue;
+                    }
+
+                    let priority = frontier_priority(entry.priority);
+                    let added = match entry.lastmod {
+                        Some(lastmod) => self.url_store.requeue_if_modified(&entry.loc, lastmod, priority),
+                        None => self.url_store.add_to_frontier_with_priority(&entry.loc, priority),
+                    };
+                    if added {
+                        self.urls_added.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Frontier priority of a sitemap URL, in the same band as linked URLs
+/// Only a `<priority>` above the default raises it, so one large sitemap doesn't starve the link frontier
+fn frontier_priority(priority: Option<f32>) -> u8 {
+    let above_default = priority.unwrap_or(DEFAULT_SITEMAP_PRIORITY) - DEFAULT_SITEMAP_PRIORITY;
+    DEFAULT_PRIORITY + (above_default.max(0.0) * SITEMAP_PRIORITY_SCALE) as u8
+}
+
+fn host_of(url: &str) -> Option<String> {
+    Url::parse(url).ok()?.host_str().map(|host| host.to_string())
+}
+
+impl Clone for SitemapIngestor {
+    fn clone(&self) -> Self {
+        Self {
+            http_client: self.http_client.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+            url_store: self.url_store.clone(),
+            seen_hosts: self.seen_hosts.clone(),
+            urls_added: self.urls_added.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    const URLSET: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url><loc>https://en.wikipedia.org/wiki/A</loc><lastmod>2024-01-31</lastmod><priority>0.8</priority></url>
+  <url><loc> https://en.wikipedia.org/wiki/B </loc><priority>7</priority></url>
+  <url><lastmod>2024-01-31</lastmod></url>
+</urlset>"#;
+
+    #[test]
+    fn parses_urlset() {
+        let Some(Sitemap::UrlSet(entries)) = parse_sitemap(URLSET.as_bytes()) else {
+            panic!("expected a urlset");
+        };
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].loc, "https://en.wikipedia.org/wiki/A");
+        assert_eq!(entries[0].lastmod, Some(1_706_659_200));
+        assert_eq!(entries[0].priority, Some(0.8));
+        assert_eq!(entries[1].loc, "https://en.wikipedia.org/wiki/B");
+        assert_eq!(entries[1].lastmod, None);
+        assert_eq!(entries[1].priority, Some(1.0));
+    }
+
+    #[test]
+    fn parses_sitemap_index() {
+        let index = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+            <sitemap><loc>https://en.wikipedia.org/sitemap-1.xml.gz</loc><lastmod>2024-01-31T12:00:00+00:00</lastmod></sitemap>
+            <sitemap><loc>https://en.wikipedia.org/sitemap-2.xml</loc></sitemap>
+        </sitemapindex>"#;
+        let Some(Sitemap::Index(children)) = parse_sitemap(index.as_bytes()) else {
+            panic!("expected a sitemap index");
+        };
+        let locs: Vec<&str> = children.iter().map(|child| child.loc.as_str()).collect();
+        assert_eq!(locs, ["https://en.wikipedia.org/sitemap-1.xml.gz", "https://en.wikipedia.org/sitemap-2.xml"]);
+        assert_eq!(children[0].lastmod, Some(1_706_702_400));
+
+        assert!(parse_sitemap(b"<rss><channel></channel></rss>").is_none());
+    }
+
+    #[test]
+    fn parses_plain_text_sitemap() {
+        let text = "\u{feff}https://en.wikipedia.org/wiki/A\n\n# comment\nftp://example.org/file\nhttp://en.wikipedia.org/wiki/B\n";
+        let Some(Sitemap::UrlSet(entries)) = parse_sitemap(text.as_bytes()) else {
+            panic!("expected a urlset");
+        };
+        let locs: Vec<&str> = entries.iter().map(|entry| entry.loc.as_str()).collect();
+        assert_eq!(locs, ["https://en.wikipedia.org/wiki/A", "http://en.wikipedia.org/wiki/B"]);
+    }
+
+    #[test]
+    fn parses_gzipped_sitemap() {
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(URLSET.as_bytes()).unwrap();
+        let body = decompress_if_gzipped(encoder.finish().unwrap()).unwrap();
+        assert!(matches!(parse_sitemap(&body), Some(Sitemap::UrlSet(entries)) if entries.len() == 2));
+
+        // Bodies without the gzip magic bytes pass through
+        assert_eq!(decompress_if_gzipped(URLSET.as_bytes().to_vec()).unwrap(), URLSET.as_bytes());
+    }
+
+    #[test]
+    fn parses_lastmod_formats() {
+        assert_eq!(parse_lastmod("2024-01-31"), Some(1_706_659_200));
+        assert_eq!(parse_lastmod("2024-01-31T12:00:00Z"), Some(1_706_702_400));
+        assert_eq!(parse_lastmod("2024-01-31T14:00:00+02:00"), Some(1_706_702_400));
+        assert_eq!(parse_lastmod("31/01/2024"), None);
+        assert_eq!(parse_lastmod("1960-01-01"), None);
+    }
+
+    #[test]
+    fn only_raised_priorities_lift_sitemap_urls() {
+        assert_eq!(frontier_priority(None), DEFAULT_PRIORITY);
+        assert_eq!(frontier_priority(Some(0.1)), DEFAULT_PRIORITY);
+        assert_eq!(frontier_priority(Some(0.5)), DEFAULT_PRIORITY);
+        assert_eq!(frontier_priority(Some(0.8)), DEFAULT_PRIORITY + 30);
+        assert_eq!(frontier_priority(Some(1.0)), DEFAULT_PRIORITY + 50);
+    }
+}
@@ -0,0 +1,189 @@
+/// Bytes of the body examined when sniffing
+pub const RESOURCE_HEADER_SIZE: usize = 1445;
+
+/// Content-type headers Apache sends for files it knows nothing about
+const APACHE_BUG_TYPES: [&str; 4] = [
+    "text/plain",
+    "text/plain; charset=ISO-8859-1",
+    "text/plain; charset=iso-8859-1",
+    "text/plain; charset=UTF-8",
+];
+
+/// Tags that identify HTML when found at the start of a body, each followed by a space or `>`
+const HTML_PATTERNS: [&[u8]; 17] = [
+    b"<!DOCTYPE HTML", b"<HTML", b"<HEAD", b"<SCRIPT", b"<IFRAME", b"<H1", b"<DIV",
+    b"<FONT", b"<TABLE", b"<A", b"<STYLE", b"<TITLE", b"<B", b"<BODY", b"<BR", b"<P",
+    b"<!--",
+];
+
+/// Exact signatures for non-scriptable types, checked after the HTML patterns
+const SIGNATURES: [(&[u8], &str); 13] = [
+    (b"%PDF-", "application/pdf"),
+    (b"%!PS-Adobe-", "application/postscript"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"BM", "image/bmp"),
+    (b"\x00\x00\x01\x00", "image/x-icon"),
+    (b"OggS\x00", "application/ogg"),
+    (b"ID3", "audio/mpeg"),
+    (b"\x1f\x8b\x08", "application/x-gzip"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"Rar!\x1a\x07\x00", "application/x-rar-compressed"),
+];
+
+/// Whether the computed MIME type of a response depends on its body
+pub fn is_sniffable(content_type: Option<&str>, no_sniff: bool) -> bool {
+    match content_type {
+        None => true,
+        Some(header) => {
+            let essence = essence(header);
+            is_unknown(&essence) || (!no_sniff && APACHE_BUG_TYPES.contains(&header.trim()))
+        }
+    }
+}
+
+/// Compute the MIME type of a response from its content-type header and body
+/// Follows https://mimesniff.spec.whatwg.org/ for unknown types and the Apache `text/plain` bug
+pub fn sniff_mime_type(content_type: Option<&str>, no_sniff: bool, body: &[u8]) -> String {
+    let header = &body[..body.len().min(RESOURCE_HEADER_SIZE)];
+
+    let Some(content_type) = content_type else {
+        return sniff_unknown(header, !no_sniff);
+    };
+    let supplied = essence(content_type);
+
+    if is_unknown(&supplied) {
+        return sniff_unknown(header, !no_sniff);
+    }
+    if no_sniff {
+        return supplied;
+    }
+    if APACHE_BUG_TYPES.contains(&content_type.trim()) {
+        return sniff_text_or_binary(header);
+    }
+
+    supplied
+}
+
+fn essence(content_type: &str) -> String {
+    content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase()
+}
+
+fn is_unknown(essence: &str) -> bool {
+    matches!(essence, "" | "unknown/unknown" | "application/unknown" | "*/*")
+}
+
+/// Rules for identifying an unknown MIME type
+fn sniff_unknown(header: &[u8], sniff_scriptable: bool) -> String {
+    if sniff_scriptable {
+        let trimmed = skip_whitespace(header);
+        for pattern in HTML_PATTERNS {
+            if trimmed.len() > pattern.len()
+                && trimmed[..pattern.len()].eq_ignore_ascii_case(pattern)
+                && matches!(trimmed[pattern.len()], b' ' | b'>') {
+                return "text/html".to_string();
+            }
+        }
+        if trimmed.starts_with(b"<?xml") {
+            return "text/xml".to_string();
+        }
+    }
+
+    if let Some(mime_type) = sniff_bom(header) {
+        return mime_type;
+    }
+    if let Some((_, mime_type)) = SIGNATURES.iter().find(|(signature, _)| header.starts_with(signature)) {
+        return mime_type.to_string();
+    }
+    if header.len() >= 14 && &header[..4] == b"RIFF" && &header[8..14] == b"WEBPVP" {
+        return "image/webp".to_string();
+    }
+
+    if header.iter().any(|&b| is_binary_byte(b)) {
+        "application/octet-stream".to_string()
+    } else {
+        "text/plain".to_string()
+    }
+}
+
+/// Rules for distinguishing if a resource is text or binary
+fn sniff_text_or_binary(header: &[u8]) -> String {
+    if let Some(mime_type) = sniff_bom(header) {
+        return mime_type;
+    }
+    if !header.iter().any(|&b| is_binary_byte(b)) {
+        return "text/plain".to_string();
+    }
+    sniff_unknown(header, false)
+}
+
+fn sniff_bom(header: &[u8]) -> Option<String> {
+    let has_bom = header.starts_with(b"\xfe\xff")
+        || header.starts_with(b"\xff\xfe")
+        || header.starts_with(b"\xef\xbb\xbf");
+    has_bom.then(|| "text/plain".to_string())
+}
+
+fn skip_whitespace(header: &[u8]) -> &[u8] {
+    let start = header
+        .iter()
+        .position(|b| !matches!(b, b'\t' | b'\n' | b'\x0c' | b'\r' | b' '))
+        .unwrap_or(header.len());
+    &header[start..]
+}
+
+fn is_binary_byte(b: u8) -> bool {
+    matches!(b, 0x00..=0x08 | 0x0b | 0x0e..=0x1a | 0x1c..=0x1f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_types_are_sniffed_from_the_body() {
+        assert_eq!(sniff_mime_type(None, false, b"\n  <!doctype html><html>"), "text/html");
+        assert_eq!(sniff_mime_type(Some("unknown/unknown"), false, b"<p>Hello</p>"), "text/html");
+        assert_eq!(sniff_mime_type(Some(""), false, b"<?xml version=\"1.0\"?><rss/>"), "text/xml");
+        assert_eq!(sniff_mime_type(None, false, b"%PDF-1.7\n"), "application/pdf");
+        assert_eq!(sniff_mime_type(None, false, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), "image/png");
+        assert_eq!(sniff_mime_type(None, false, b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(sniff_mime_type(None, false, b"plain words"), "text/plain");
+        assert_eq!(sniff_mime_type(None, false, b"\x00\x01\x02binary"), "application/octet-stream");
+        // Tags only count when followed by a space or `>`
+        assert_eq!(sniff_mime_type(None, false, b"<Pre-formatted"), "text/plain");
+    }
+
+    #[test]
+    fn nosniff_keeps_scriptable_types_out_of_unknown_bodies() {
+        assert_eq!(sniff_mime_type(None, true, b"<html><body>"), "text/plain");
+        assert_eq!(sniff_mime_type(None, true, b"GIF89a\x01\x00"), "image/gif");
+    }
+
+    #[test]
+    fn supplied_types_are_kept() {
+        assert_eq!(sniff_mime_type(Some("Text/HTML; charset=utf-8"), false, b"%PDF-1.7"), "text/html");
+        assert_eq!(sniff_mime_type(Some("application/json"), false, b"<html>"), "application/json");
+    }
+
+    #[test]
+    fn apache_text_plain_is_checked_for_binary() {
+        assert_eq!(sniff_mime_type(Some("text/plain"), false, b"just text"), "text/plain");
+        assert_eq!(sniff_mime_type(Some("text/plain; charset=UTF-8"), false, b"%PDF-1.7\n\x00\x01"), "application/pdf");
+        assert_eq!(sniff_mime_type(Some("text/plain"), false, b"\xef\xbb\xbf\x00\x01"), "text/plain");
+        // Sniffing never turns text/plain into HTML, and nosniff trusts the header
+        assert_eq!(sniff_mime_type(Some("text/plain"), false, b"<html>\x00"), "application/octet-stream");
+        assert_eq!(sniff_mime_type(Some("text/plain"), true, b"%PDF-1.7\n\x00"), "text/plain");
+    }
+
+    #[test]
+    fn sniffable_responses() {
+        assert!(is_sniffable(None, true));
+        assert!(is_sniffable(Some("*/*"), true));
+        assert!(is_sniffable(Some("text/plain; charset=ISO-8859-1"), false));
+        assert!(!is_sniffable(Some("text/plain; charset=ISO-8859-1"), true));
+        assert!(!is_sniffable(Some("text/html"), false));
+    }
+}
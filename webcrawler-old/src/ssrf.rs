@@ -0,0 +1,168 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use url::{Host, Url};
+
+/// IPv4 ranges that are never crawled unless allowlisted
+const BLOCKED_V4: [(Ipv4Addr, u8); 13] = [
+    (Ipv4Addr::new(0, 0, 0, 0), 8),        // "this" network
+    (Ipv4Addr::new(10, 0, 0, 0), 8),       // RFC1918
+    (Ipv4Addr::new(100, 64, 0, 0), 10),    // carrier-grade NAT, includes 100.100.100.200 metadata
+    (Ipv4Addr::new(127, 0, 0, 0), 8),      // loopback
+    (Ipv4Addr::new(169, 254, 0, 0), 16),   // link-local, includes 169.254.169.254 metadata
+    (Ipv4Addr::new(172, 16, 0, 0), 12),    // RFC1918
+    (Ipv4Addr::new(192, 0, 0, 0), 24),     // IETF protocol assignments
+    (Ipv4Addr::new(192, 0, 2, 0), 24),     // documentation
+    (Ipv4Addr::new(192, 168, 0, 0), 16),   // RFC1918
+    (Ipv4Addr::new(198, 18, 0, 0), 15),    // benchmarking
+    (Ipv4Addr::new(198, 51, 100, 0), 24),  // documentation
+    (Ipv4Addr::new(203, 0, 113, 0), 24),   // documentation
+    (Ipv4Addr::new(224, 0, 0, 0), 3),      // multicast and reserved, up to broadcast
+];
+
+/// IPv6 ranges that are never crawled unless allowlisted
+const BLOCKED_V6: [(Ipv6Addr, u8); 6] = [
+    (Ipv6Addr::UNSPECIFIED, 128),
+    (Ipv6Addr::LOCALHOST, 128),
+    (Ipv6Addr::new(0xfc00, 0, 0, 0, 0, 0, 0, 0), 7),     // unique local, includes fd00:ec2::254 metadata
+    (Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), 10),    // link-local
+    (Ipv6Addr::new(0xff00, 0, 0, 0, 0, 0, 0, 0), 8),     // multicast
+    (Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), 32), // documentation
+];
+
+/// Refuses destinations in private, loopback, link-local, multicast and metadata ranges
+/// Allowlist entries are CIDR ranges, single IPs, or host names that also cover their subdomains
+pub struct AddressGuard {
+    allowed_networks: Vec<(IpAddr, u8)>,
+    allowed_hosts: Vec<String>,
+}
+
+impl AddressGuard {
+    pub fn new(allowlist: &[String]) -> Self {
+        let mut allowed_networks = Vec::new();
+        let mut allowed_hosts = Vec::new();
+        for entry in allowlist {
+            match parse_network(entry) {
+                Some(network) => allowed_networks.push(network),
+                None => allowed_hosts.push(entry.trim().trim_start_matches('.').to_ascii_lowercase()),
+            }
+        }
+
+        Self {
+            allowed_networks,
+            allowed_hosts,
+        }
+    }
+
+    /// Whether `host` may be reached at `ip`
+    pub fn permits(&self, host: &str, ip: IpAddr) -> bool {
+        !is_restricted(ip)
+            || self.host_allowed(host)
+            || self.allowed_networks.iter().any(|&(network, prefix)| in_network(ip, network, prefix))
+    }
+
+    /// Whether a URL's host is an IP literal that may not be reached
+    /// Host names are checked when they are resolved, so they always pass here
+    pub fn blocks_url(&self, url: &Url) -> bool {
+        let ip = match url.host() {
+            Some(Host::Ipv4(ip)) => IpAddr::V4(ip),
+            Some(Host::Ipv6(ip)) => IpAddr::V6(ip),
+            _ => return false,
+        };
+        !self.permits(url.host_str().unwrap_or_default(), ip)
+    }
+
+    fn host_allowed(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        self.allowed_hosts.iter().any(|allowed| {
+            host == *allowed || host.strip_suffix(allowed.as_str()).is_some_and(|prefix| prefix.ends_with('.'))
+        })
+    }
+}
+
+/// Whether an address falls in a range that is blocked by default
+pub fn is_restricted(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => BLOCKED_V4.iter().any(|&(network, prefix)| in_network(IpAddr::V4(v4), IpAddr::V4(network), prefix)),
+        IpAddr::V6(v6) => {
+            // IPv4-mapped, IPv4-compatible, NAT64, 6to4 and Teredo addresses reach an embedded IPv4 address
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_restricted(IpAddr::V4(v4));
+            }
+            let segments = v6.segments();
+            // Deprecated IPv4-compatible ::AABB:CCDD, other than :: and ::1 which the v6 table covers
+            if segments[..6] == [0; 6] && !v6.is_unspecified() && !v6.is_loopback() {
+                return is_restricted(IpAddr::V4(embedded_v4(segments[6], segments[7])));
+            }
+            if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                return is_restricted(IpAddr::V4(embedded_v4(segments[6], segments[7])));
+            }
+            // 6to4, 2002:AABB:CCDD::/48 for AA.BB.CC.DD
+            if segments[0] == 0x2002 {
+                return is_restricted(IpAddr::V4(embedded_v4(segments[1], segments[2])));
+            }
+            // Teredo, 2001:0::/32, with the server's address and the client's inverted in the last 32 bits
+            if segments[..2] == [0x2001, 0] {
+                return is_restricted(IpAddr::V4(embedded_v4(segments[2], segments[3])))
+                    || is_restricted(IpAddr::V4(embedded_v4(!segments[6], !segments[7])));
+            }
+            BLOCKED_V6.iter().any(|&(network, prefix)| in_network(IpAddr::V6(v6), IpAddr::V6(network), prefix))
+        }
+    }
+}
+
+fn embedded_v4(high: u16, low: u16) -> Ipv4Addr {
+    Ipv4Addr::from((u32::from(high) << 16) | u32::from(low))
+}
+
+fn in_network(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+/// Parse `10.0.0.0/8`, `fd00::/8` or a single address
+fn parse_network(entry: &str) -> Option<(IpAddr, u8)> {
+    let entry = entry.trim();
+    let (address, prefix) = match entry.split_once('/') {
+        Some((address, prefix)) => (address, Some(prefix.parse::<u8>().ok()?)),
+        None => (entry, None),
+    };
+    let ip: IpAddr = address.trim_matches(|c| c == '[' || c == ']').parse().ok()?;
+    let max_prefix = if ip.is_ipv4() { 32 } else { 128 };
+    let prefix = prefix.unwrap_or(max_prefix);
+    (prefix <= max_prefix).then_some((ip, prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restricted(ip: &str) -> bool {
+        is_restricted(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn embedded_ipv4_addresses_are_checked() {
+        assert!(restricted("::ffff:127.0.0.1"));
+        assert!(restricted("64:ff9b::a9fe:a9fe"));
+        // IPv4-compatible forms of 127.0.0.1 and 169.254.169.254
+        assert!(restricted("::7f00:1"));
+        assert!(restricted("::a9fe:a9fe"));
+        assert!(restricted("::1"));
+        assert!(!restricted("::5db8:d822"));
+        // 6to4 of 127.0.0.1 and 169.254.169.254
+        assert!(restricted("2002:7f00:1::1"));
+        assert!(restricted("2002:a9fe:a9fe::"));
+        assert!(!restricted("2002:5db8:d822::1"));
+        // Teredo through a public server to client 10.0.0.1, stored inverted
+        assert!(restricted("2001:0:4136:e378:8000:63bf:f5ff:fffe"));
+        assert!(!restricted("2001:0:4136:e378:8000:63bf:a247:4bfe"));
+    }
+}
@@ -0,0 +1,392 @@
+use crate::normalize::{collapse_whitespace, decode_attribute, decode_entities};
+use lol_html::html_content::Element;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use url::Url;
+
+/// Selector for the script elements that carry JSON-LD
+pub const JSON_LD_SELECTOR: &str = r#"script[type="application/ld+json"]"#;
+/// Selector for the elements that open or contribute to a microdata item
+pub const MICRODATA_SELECTOR: &str = "[itemscope], [itemprop]";
+
+/// Vocabulary prefixes stripped from types and property names, so `https://schema.org/Article` is `Article`
+const VOCABULARY_PREFIXES: [&str; 3] = ["https://schema.org", "http://schema.org", "schema:"];
+/// Deeper nesting is dropped, so hostile documents can't recurse without bound
+const MAX_DEPTH: usize = 16;
+const MAX_ENTITIES: usize = 256;
+/// Text-valued microdata properties are cut at this many source characters
+const MAX_PROPERTY_CHARS: usize = 10_000;
+
+/// Where an entity was found on the page
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum EntityFormat {
+    #[default]
+    #[serde(rename = "json-ld")]
+    JsonLd,
+    #[serde(rename = "microdata")]
+    Microdata,
+}
+
+/// A schema.org item such as an Article, Product, Recipe, Event or BreadcrumbList
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Entity {
+    /// Types without their vocabulary prefix, empty for untyped nested values such as a plain address
+    pub types: Vec<String>,
+    /// `@id` or `itemid`
+    pub id: Option<String>,
+    pub format: EntityFormat,
+    /// Values by property name, each list in page order
+    pub properties: BTreeMap<String, Vec<PropertyValue>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PropertyValue {
+    Text(String),
+    Entity(Box<Entity>),
+}
+
+impl Entity {
+    fn new(format: EntityFormat) -> Self {
+        Self {
+            format,
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, name: &str, value: PropertyValue) {
+        self.properties.entry(strip_vocabulary(name).to_string()).or_default().push(value);
+    }
+}
+
+fn strip_vocabulary(name: &str) -> &str {
+    let name = name.trim();
+    VOCABULARY_PREFIXES
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .map(|stripped| stripped.trim_start_matches('/'))
+        .filter(|stripped| !stripped.is_empty())
+        .unwrap_or(name)
+}
+
+/// Entities in the body of a JSON-LD script, None when it is not valid JSON
+/// Top-level arrays and `@graph` lists are flattened; only typed items are kept at the top level
+pub fn parse_json_ld(script: &str) -> Option<Vec<Entity>> {
+    let value: Value = serde_json::from_str(script.trim()).ok()?;
+    let mut entities = Vec::new();
+    collect_json_ld(&value, &mut entities, 0);
+    entities.truncate(MAX_ENTITIES);
+    Some(entities)
+}
+
+fn collect_json_ld(value: &Value, entities: &mut Vec<Entity>, depth: usize) {
+    if depth > MAX_DEPTH || entities.len() >= MAX_ENTITIES {
+        return;
+    }
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                collect_json_ld(item, entities, depth + 1);
+            }
+        }
+        Value::Object(object) => {
+            if let Some(graph) = object.get("@graph") {
+                collect_json_ld(graph, entities, depth + 1);
+            }
+            if object.contains_key("@type") {
+                entities.push(json_ld_entity(object, depth));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn json_ld_entity(object: &serde_json::Map<String, Value>, depth: usize) -> Entity {
+    let mut entity = Entity::new(EntityFormat::JsonLd);
+    entity.types = match object.get("@type") {
+        Some(Value::String(kind)) => vec![strip_vocabulary(kind).to_string()],
+        Some(Value::Array(kinds)) => kinds.iter().filter_map(Value::as_str).map(|kind| strip_vocabulary(kind).to_string()).collect(),
+        _ => Vec::new(),
+    };
+    entity.id = object.get("@id").and_then(Value::as_str).map(str::to_string);
+
+    for (name, value) in object {
+        if name.starts_with('@') {
+            continue;
+        }
+        for value in json_ld_values(value, depth + 1) {
+            entity.add(name, value);
+        }
+    }
+    entity
+}
+
+fn json_ld_values(value: &Value, depth: usize) -> Vec<PropertyValue> {
+    if depth > MAX_DEPTH {
+        return Vec::new();
+    }
+    match value {
+        Value::Null => Vec::new(),
+        Value::Bool(b) => vec![PropertyValue::Text(b.to_string())],
+        Value::Number(n) => vec![PropertyValue::Text(n.to_string())],
+        Value::String(s) => vec![PropertyValue::Text(s.trim().to_string())],
+        Value::Array(items) => items.iter().flat_map(|item| json_ld_values(item, depth + 1)).collect(),
+        // Value objects (`{"@value": ...}`) stand for their value
+        Value::Object(object) => match object.get("@value") {
+            Some(inner) => json_ld_values(inner, depth + 1),
+            None => vec![PropertyValue::Entity(Box::new(json_ld_entity(object, depth)))],
+        },
+    }
+}
+
+/// An element still open while its microdata is collected
+enum OpenItem {
+    /// An `itemscope` element, with the property names it fills in its parent item
+    Scope { serial: usize, props: Vec<String>, entity: Entity },
+    /// An `itemprop` element whose value is its text content
+    Text { serial: usize, props: Vec<String>, text: String },
+}
+
+impl OpenItem {
+    fn serial(&self) -> usize {
+        match self {
+            OpenItem::Scope { serial, .. } | OpenItem::Text { serial, .. } => *serial,
+        }
+    }
+}
+
+/// Builds microdata items from lol_html element and text events
+/// Elements are closed from their end tag handler; ones whose end tag never fires are closed
+/// along with their parent, or when the collector finishes
+pub struct MicrodataCollector {
+    base_url: Url,
+    open: Vec<OpenItem>,
+    next_serial: usize,
+    entities: Vec<Entity>,
+}
+
+impl MicrodataCollector {
+    pub fn new(base_url: Url) -> Self {
+        Self {
+            base_url,
+            open: Vec::new(),
+            next_serial: 0,
+            entities: Vec::new(),
+        }
+    }
+
+    /// Resolve URL-valued properties against a `<base href>` from here on
+    pub fn set_base_url(&mut self, base_url: Url) {
+        self.base_url = base_url;
+    }
+
+    /// Handle an element with `itemscope` or `itemprop`
+    /// Returns a serial to pass to `close` from the element's end tag, or None if nothing stays open
+    pub fn open(&mut self, el: &Element) -> Option<usize> {
+        let props: Vec<String> = el
+            .get_attribute("itemprop")
+            .map(|props| props.split_ascii_whitespace().map(str::to_string).collect())
+            .unwrap_or_default();
+
+        let serial = self.next_serial;
+        self.next_serial += 1;
+
+        if el.has_attribute("itemscope") {
+            if self.open.len() >= MAX_DEPTH {
+                return None;
+            }
+            let mut entity = Entity::new(EntityFormat::Microdata);
+            entity.types = el
+                .get_attribute("itemtype")
+                .map(|types| types.split_ascii_whitespace().map(|kind| strip_vocabulary(kind).to_string()).collect())
+                .unwrap_or_default();
+            entity.id = el.get_attribute("itemid").map(|id| decode_attribute(&id));
+            self.open.push(OpenItem::Scope { serial, props, entity });
+        } else if props.is_empty() {
+            return None;
+        } else if let Some(value) = self.attribute_value(el) {
+            self.add_to_scope(&props, PropertyValue::Text(value));
+            return None;
+        } else {
+            self.open.push(OpenItem::Text { serial, props, text: String::new() });
+        }
+
+        if el.can_have_content() {
+            Some(serial)
+        } else {
+            self.close(serial);
+            None
+        }
+    }
+
+    /// Add a raw text chunk to the text-valued properties that are open
+    pub fn push_text(&mut self, chunk: &str) {
+        for item in &mut self.open {
+            if let OpenItem::Text { text, .. } = item
+                && text.len() < MAX_PROPERTY_CHARS {
+                text.push_str(chunk);
+            }
+        }
+    }
+
+    /// Close the element with this serial and anything opened inside it that is still open
+    pub fn close(&mut self, serial: usize) {
+        if !self.open.iter().any(|item| item.serial() == serial) {
+            return;
+        }
+        while let Some(item) = self.open.pop() {
+            let closed = item.serial();
+            self.finish_item(item);
+            if closed == serial {
+                break;
+            }
+        }
+    }
+
+    /// Close whatever is still open and take the collected items
+    pub fn finish(&mut self) -> Vec<Entity> {
+        while let Some(item) = self.open.pop() {
+            self.finish_item(item);
+        }
+        std::mem::take(&mut self.entities)
+    }
+
+    fn finish_item(&mut self, item: OpenItem) {
+        match item {
+            OpenItem::Text { props, text, .. } => {
+                let text = collapse_whitespace(&decode_entities(&text));
+                self.add_to_scope(&props, PropertyValue::Text(text));
+            }
+            OpenItem::Scope { props, entity, .. } => {
+                let has_parent = self.open.iter().any(|item| matches!(item, OpenItem::Scope { .. }));
+                if props.is_empty() || !has_parent {
+                    if self.entities.len() < MAX_ENTITIES {
+                        self.entities.push(entity);
+                    }
+                } else {
+                    self.add_to_scope(&props, PropertyValue::Entity(Box::new(entity)));
+                }
+            }
+        }
+    }
+
+    /// Add a property to the innermost open item; properties outside any item are dropped
+    fn add_to_scope(&mut self, props: &[String], value: PropertyValue) {
+        let scope = self.open.iter_mut().rev().find_map(|item| match item {
+            OpenItem::Scope { entity, .. } => Some(entity),
+            OpenItem::Text { .. } => None,
+        });
+        if let Some(entity) = scope {
+            for prop in props {
+                entity.add(prop, value.clone());
+            }
+        }
+    }
+
+    /// Value of a property held in an attribute rather than in the element's text
+    fn attribute_value(&self, el: &Element) -> Option<String> {
+        let tag = el.tag_name().to_ascii_lowercase();
+        let (attribute, is_url) = match tag.as_str() {
+            "meta" => ("content", false),
+            "audio" | "embed" | "iframe" | "img" | "source" | "track" | "video" => ("src", true),
+            "a" | "area" | "link" => ("href", true),
+            "object" => ("data", true),
+            "data" | "meter" => ("value", false),
+            "time" => ("datetime", false),
+            _ => return None,
+        };
+        let value = decode_attribute(&el.get_attribute(attribute)?);
+        if is_url {
+            self.base_url.join(value.trim()).ok().map(|url| url.to_string())
+        } else {
+            Some(value.trim().to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::Extractors;
+    use crate::parser::parse_html;
+
+    fn text(entity: &Entity, name: &str) -> Vec<String> {
+        entity
+            .properties
+            .get(name)
+            .into_iter()
+            .flatten()
+            .filter_map(|value| match value {
+                PropertyValue::Text(text) => Some(text.clone()),
+                PropertyValue::Entity(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn json_ld_flattens_graphs_and_keeps_typed_items() {
+        let script = r##"{
+            "@context": "https://schema.org",
+            "@graph": [
+                {"@type": "https://schema.org/Article", "@id": "#article", "headline": " Full-text search ", "wordCount": 1200,
+                 "author": {"@type": "Person", "name": "Ada"}},
+                {"@type": ["schema:BreadcrumbList", "ItemList"]},
+                {"name": "untyped"}
+            ]
+        }"##;
+        let entities = parse_json_ld(script).unwrap();
+        assert_eq!(entities.len(), 2);
+        assert_eq!(entities[0].types, ["Article"]);
+        assert_eq!(entities[0].id.as_deref(), Some("#article"));
+        assert_eq!(text(&entities[0], "headline"), ["Full-text search"]);
+        assert_eq!(text(&entities[0], "wordCount"), ["1200"]);
+        let Some([PropertyValue::Entity(author)]) = entities[0].properties.get("author").map(Vec::as_slice) else {
+            panic!("expected a nested author");
+        };
+        assert_eq!(author.types, ["Person"]);
+        assert_eq!(entities[1].types, ["BreadcrumbList", "ItemList"]);
+    }
+
+    #[test]
+    fn types_keep_names_that_only_look_like_a_vocabulary() {
+        let entities = parse_json_ld(r#"[{"@type": "http://schema.org/"}, {"@type": "schemaorgThing"}, {"@type": "https://example.org/Thing"}]"#).unwrap();
+        let types: Vec<&str> = entities.iter().flat_map(|entity| entity.types.iter().map(String::as_str)).collect();
+        assert_eq!(types, ["http://schema.org/", "schemaorgThing", "https://example.org/Thing"]);
+    }
+
+    #[test]
+    fn invalid_json_ld_scripts_are_counted() {
+        assert!(parse_json_ld("{\"@type\": \"Article\",}").is_none());
+        let html = r#"<head>
+            <script type="application/ld+json">{"@type": "Article", "headline": "Kept"}</script>
+            <script type="application/ld+json">{"@type": "Article", </script>
+            <script type="application/ld+json">not json</script>
+            <script type="application/json">{"@type": "Ignored"}</script>
+        </head>"#;
+        let parsed = parse_html(html.to_string(), "https://en.wikipedia.org/wiki/Page", &Extractors::new()).unwrap();
+        assert_eq!(parsed.invalid_json_ld, 2);
+        assert_eq!(parsed.structured_data.len(), 1);
+        assert_eq!(text(&parsed.structured_data[0], "headline"), ["Kept"]);
+    }
+
+    #[test]
+    fn microdata_items_nest_by_scope() {
+        let html = r#"<div itemscope itemtype="https://schema.org/Product">
+            <span itemprop="name">Lamp</span>
+            <div itemprop="offers" itemscope itemtype="https://schema.org/Offer">
+                <meta itemprop="price" content="19.99">
+            </div>
+        </div>"#;
+        let parsed = parse_html(html.to_string(), "https://en.wikipedia.org/wiki/Page", &Extractors::new()).unwrap();
+        assert_eq!(parsed.structured_data.len(), 1);
+        let product = &parsed.structured_data[0];
+        assert_eq!((product.types.as_slice(), product.format), (["Product".to_string()].as_slice(), EntityFormat::Microdata));
+        assert_eq!(text(product, "name"), ["Lamp"]);
+        let Some([PropertyValue::Entity(offer)]) = product.properties.get("offers").map(Vec::as_slice) else {
+            panic!("expected a nested offer");
+        };
+        assert_eq!(offer.types, ["Offer"]);
+        assert_eq!(text(offer, "price"), ["19.99"]);
+    }
+}
@@ -1,5 +1,5 @@
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -8,7 +8,7 @@ use ratatui::{
     layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Bar, BarChart, BarGroup, Block, Borders, Paragraph, Sparkline},
+    widgets::{Block, Borders, Paragraph, Sparkline},
     Terminal,
 };
 use std::collections::{HashMap, VecDeque};
@@ -17,16 +17,26 @@ use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+use crate::proxy::ProxyPool;
+
 pub struct CrawlerStats {
     pub pages_crawled: Arc<AtomicUsize>,
     pub pages_written: Arc<AtomicUsize>,
     pub queue_size: Arc<AtomicUsize>,
     pub active_workers: Arc<AtomicUsize>,
+    pub sitemap_urls: Arc<AtomicUsize>,
+    pub feed_urls: Arc<AtomicUsize>,
+    pub bytes_saved: Arc<AtomicUsize>,
+    pub parse_errors: Arc<AtomicUsize>,
+    pub invalid_json_ld: Arc<AtomicUsize>,
+    /// Pages dropped as exact duplicates of a page already written
+    pub duplicates: Arc<AtomicUsize>,
     pub errors: Arc<Mutex<VecDeque<String>>>,
     pub rate_history: Arc<Mutex<VecDeque<u64>>>,
     pub domain_counts: Arc<Mutex<HashMap<String, usize>>>,
     pub start_time: Instant,
     pub should_stop: Arc<AtomicBool>,
+    pub proxies: Option<Arc<ProxyPool>>,
 }
 
 impl CrawlerStats {
@@ -40,11 +50,18 @@ impl CrawlerStats {
             pages_written,
             queue_size,
             active_workers: Arc::new(AtomicUsize::new(0)),
+            sitemap_urls: Arc::new(AtomicUsize::new(0)),
+            feed_urls: Arc::new(AtomicUsize::new(0)),
+            bytes_saved: Arc::new(AtomicUsize::new(0)),
+            parse_errors: Arc::new(AtomicUsize::new(0)),
+            invalid_json_ld: Arc::new(AtomicUsize::new(0)),
+            duplicates: Arc::new(AtomicUsize::new(0)),
             errors: Arc::new(Mutex::new(VecDeque::with_capacity(10))),
             rate_history: Arc::new(Mutex::new(VecDeque::with_capacity(60))),
             domain_counts: Arc::new(Mutex::new(HashMap::new())),
             start_time: Instant::now(),
             should_stop: Arc::new(AtomicBool::new(false)),
+            proxies: None,
         }
     }
 
@@ -116,6 +133,13 @@ async fn run_ui_loop(
         let pages_written = stats.pages_written.load(Ordering::Relaxed);
         let queue_size = stats.queue_size.load(Ordering::Relaxed);
         let active_workers = stats.active_workers.load(Ordering::Relaxed);
+        let sitemap_urls = stats.sitemap_urls.load(Ordering::Relaxed);
+        let feed_urls = stats.feed_urls.load(Ordering::Relaxed);
+        let bytes_saved = stats.bytes_saved.load(Ordering::Relaxed);
+        let parse_errors = stats.parse_errors.load(Ordering::Relaxed);
+        let invalid_json_ld = stats.invalid_json_ld.load(Ordering::Relaxed);
+        let duplicates = stats.duplicates.load(Ordering::Relaxed);
+        let proxy_snapshots = stats.proxies.as_ref().map(|pool| pool.snapshot()).unwrap_or_default();
         let elapsed = stats.start_time.elapsed();
 
         // Update rate history every second
@@ -142,9 +166,15 @@ async fn run_ui_loop(
                 .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
                 .split(vertical_chunks[0]);
 
+            // Bottom row gains a third column for the proxy pool when proxies are configured
+            let bottom_constraints = if proxy_snapshots.is_empty() {
+                vec![Constraint::Percentage(50), Constraint::Percentage(50)]
+            } else {
+                vec![Constraint::Percentage(30), Constraint::Percentage(30), Constraint::Percentage(40)]
+            };
             let bottom_chunks = Layout::default()
                 .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .constraints(bottom_constraints)
                 .split(vertical_chunks[1]);
 
             // Top-left: System info (neofetch style)
@@ -158,6 +188,13 @@ async fn run_ui_loop(
                 0.0
             };
 
+            // Share of the pages that would have been written
+            let duplicate_rate = if pages_written + duplicates > 0 {
+                duplicates as f64 * 100.0 / (pages_written + duplicates) as f64
+            } else {
+                0.0
+            };
+
             let worker_status = if active_workers > 0 {
                 Span::styled(
                     format!("{} Active", active_workers),
@@ -228,6 +265,48 @@ async fn run_ui_loop(
                     Span::styled("  Queue     : ", Style::default().fg(Color::Cyan)),
                     queue_status,
                 ]),
+                Line::from(vec![
+                    Span::styled("  Sitemaps  : ", Style::default().fg(Color::Cyan)),
+                    Span::styled(
+                        format!("{} URLs", sitemap_urls),
+                        Style::default().fg(Color::White),
+                    ),
+                ]),
+                Line::from(vec![
+                    Span::styled("  Feeds     : ", Style::default().fg(Color::Cyan)),
+                    Span::styled(
+                        format!("{} URLs", feed_urls),
+                        Style::default().fg(Color::White),
+                    ),
+                ]),
+                Line::from(vec![
+                    Span::styled("  Saved     : ", Style::default().fg(Color::Cyan)),
+                    Span::styled(
+                        format!("{:.1} MB", bytes_saved as f64 / (1024.0 * 1024.0)),
+                        Style::default().fg(Color::White),
+                    ),
+                ]),
+                Line::from(vec![
+                    Span::styled("  Parse errs: ", Style::default().fg(Color::Cyan)),
+                    Span::styled(
+                        format!("{}", parse_errors),
+                        Style::default().fg(if parse_errors > 0 { Color::Yellow } else { Color::White }),
+                    ),
+                ]),
+                Line::from(vec![
+                    Span::styled("  JSON-LD   : ", Style::default().fg(Color::Cyan)),
+                    Span::styled(
+                        format!("{} invalid", invalid_json_ld),
+                        Style::default().fg(if invalid_json_ld > 0 { Color::Yellow } else { Color::White }),
+                    ),
+                ]),
+                Line::from(vec![
+                    Span::styled("  Duplicates: ", Style::default().fg(Color::Cyan)),
+                    Span::styled(
+                        format!("{} ({:.1}%)", duplicates, duplicate_rate),
+                        Style::default().fg(Color::White),
+                    ),
+                ]),
             ];
 
             let system_block = Paragraph::new(system_info)
@@ -326,16 +405,51 @@ async fn run_ui_loop(
                 .max(max_rate);
 
             f.render_widget(sparkline, bottom_chunks[1]);
+
+            // Bottom-right: Proxy pool health
+            if !proxy_snapshots.is_empty() {
+                let proxy_lines: Vec<Line> = proxy_snapshots
+                    .iter()
+                    .map(|proxy| {
+                        let (marker, color) = if proxy.healthy {
+                            ("●", Color::Green)
+                        } else {
+                            ("○", Color::Red)
+                        };
+                        Line::from(vec![
+                            Span::styled(format!("  {} ", marker), Style::default().fg(color)),
+                            Span::styled(proxy.label.as_str(), Style::default().fg(Color::Cyan)),
+                            Span::styled(
+                                format!(
+                                    "  {} req, {} fail, {} ms, {} hosts",
+                                    proxy.requests, proxy.failures, proxy.avg_latency_ms, proxy.hosts
+                                ),
+                                Style::default().fg(Color::White),
+                            ),
+                        ])
+                    })
+                    .collect();
+
+                let proxy_block = Paragraph::new(proxy_lines)
+                    .block(Block::default().borders(Borders::ALL).title("Proxies"));
+                f.render_widget(proxy_block, bottom_chunks[2]);
+            }
         })?;
 
-        // Check for key press
+        // Check for key press, Ctrl-C arrives as one in raw mode
         if event::poll(Duration::from_millis(100))?
             && let Event::Key(key) = event::read()?
-            && let KeyCode::Char('q') = key.code {
+            && (key.code == KeyCode::Char('q')
+                || key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)) {
             stats.stop();
             break;
         }
 
+        // Stopped by a signal or the page limit
+        if stats.should_stop() {
+            break;
+        }
+
         // Check if done
         if pages_crawled >= max_pages {
             tokio::time::sleep(Duration::from_secs(2)).await;
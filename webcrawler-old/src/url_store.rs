@@ -1,14 +1,42 @@
+use crate::dedup::{self, MAX_SIMHASH_DISTANCE};
+use crate::normalize::normalize_url;
 use rocksdb::{DB, Options, BlockBasedOptions, ColumnFamilyDescriptor};
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 use url::Url;
 
+/// Column families opened by the store
+const COLUMN_FAMILIES: [&str; 11] = [
+    "visited", "frontier", "frontier_priority", "sitemaps", "feeds", "parked", "parked_hosts", "cookies",
+    "content_hashes", "aliases", "simhashes",
+];
+
+/// Priority for URLs discovered through plain links
+pub const DEFAULT_PRIORITY: u8 = 0;
+
+/// Fingerprints compared per band when looking up near-duplicates, bounding lookups in crowded bands
+const MAX_SIMHASH_BAND_SCAN: usize = 1_000;
+
 /// Persistent URL deduplication store using RocksDB
-/// Uses two column families:
+/// Uses the following column families:
 /// - "visited": URLs that have been crawled
 /// - "frontier": URLs discovered but not yet crawled
+/// - "frontier_priority": index of frontier URLs above the default priority
+/// - "sitemaps": sitemap URLs with the time they were last fetched
+/// - "feeds": RSS/Atom feed URLs with their polling schedule
+/// - "parked": frontier URLs of unreachable hosts, held back with their priority
+/// - "parked_hosts": unreachable hosts with the time their URLs are released
+/// - "cookies": persisted cookie jars of request profiles, as `Cookie` header values
+/// - "content_hashes": main text fingerprints with the URL of the first page that had them
+/// - "aliases": URLs of exact duplicates with the URL of the page they duplicate
+/// - "simhashes": SimHash fingerprints under each of their bands, with their near-duplicate cluster
 pub struct UrlStore {
     db: Arc<DB>,
+    /// Held across the read and write of a content hash, so concurrent duplicates see each other
+    content_hashes_lock: Arc<Mutex<()>>,
+    /// Held across the band lookup and insert of a fingerprint, so concurrent near-duplicates share a cluster
+    simhashes_lock: Arc<Mutex<()>>,
 }
 
 impl UrlStore {
@@ -27,32 +55,42 @@ impl UrlStore {
         block_opts.set_block_cache(&rocksdb::Cache::new_lru_cache(512 * 1024 * 1024));
         opts.set_block_based_table_factory(&block_opts);
         
+        let descriptors = || {
+            COLUMN_FAMILIES
+                .iter()
+                .map(|name| ColumnFamilyDescriptor::new(*name, opts.clone()))
+                .collect::<Vec<_>>()
+        };
+        
         // Try to open with column families, if it fails, destroy old DB and create new one
-        let db = match DB::open_cf_descriptors(&opts, path, vec![
-            ColumnFamilyDescriptor::new("visited", opts.clone()),
-            ColumnFamilyDescriptor::new("frontier", opts.clone())
-        ]) {
+        let db = match DB::open_cf_descriptors(&opts, path, descriptors()) {
             Ok(db) => db,
             Err(_) => {
                 // Old database format, destroy and recreate
                 eprintln!("Existing database is in old format. Creating new database...");
                 DB::destroy(&opts, path)?;
-                DB::open_cf_descriptors(&opts, path, vec![
-                    ColumnFamilyDescriptor::new("visited", opts.clone()),
-                    ColumnFamilyDescriptor::new("frontier", opts.clone())
-                ])?
+                DB::open_cf_descriptors(&opts, path, descriptors())?
             }
         };
         
         Ok(Self {
             db: Arc::new(db),
+            content_hashes_lock: Arc::new(Mutex::new(())),
+            simhashes_lock: Arc::new(Mutex::new(())),
         })
     }
     
     /// Add URL to frontier if not already visited or in frontier
     /// Returns true if added to frontier, false if already seen
     pub fn add_to_frontier(&self, url: &str) -> bool {
-        let normalized = Self::normalize_url(url);
+        self.add_to_frontier_with_priority(url, DEFAULT_PRIORITY)
+    }
+    
+    /// Add URL to frontier with a priority, higher priorities are popped first
+    /// Raises the priority of a URL already in the frontier
+    /// Returns true if added to frontier, false if already seen
+    pub fn add_to_frontier_with_priority(&self, url: &str, priority: u8) -> bool {
+        let normalized = normalize_url(url);
         let key = normalized.as_bytes();
         
         let visited_cf = self.db.cf_handle("visited").unwrap();
@@ -63,46 +101,128 @@ impl UrlStore {
             return false;
         }
         
-        // Check if already in frontier
-        if self.db.get_cf(frontier_cf, key).unwrap_or(None).is_some() {
+        // Check if already in frontier, bumping its priority if needed
+        if let Ok(Some(value)) = self.db.get_cf(frontier_cf, key) {
+            let current = Self::frontier_priority(&value);
+            if priority > current {
+                self.set_frontier_entry(key, priority, Some(current));
+            }
             return false;
         }
         
         // Add to frontier
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-            .to_le_bytes();
-        self.db.put_cf(frontier_cf, key, timestamp).unwrap_or_else(|e| {
-            eprintln!("Failed to add URL to frontier: {}", e);
-        });
+        self.set_frontier_entry(key, priority, None);
         true
     }
     
-    /// Pop a URL from the frontier and mark it as visited
+    /// Re-queue a URL whose content changed after it was last crawled
+    /// Unvisited URLs are simply added to the frontier
+    /// Returns true if the URL is now in the frontier because of this call
+    pub fn requeue_if_modified(&self, url: &str, lastmod: u64, priority: u8) -> bool {
+        let normalized = normalize_url(url);
+        let key = normalized.as_bytes();
+        let visited_cf = self.db.cf_handle("visited").unwrap();
+        
+        if let Ok(Some(bytes)) = self.db.get_cf(visited_cf, key)
+            && bytes.len() >= 8 {
+            let crawled_at = u64::from_le_bytes(bytes[..8].try_into().unwrap_or([0u8; 8]));
+            if crawled_at >= lastmod {
+                return false;
+            }
+            self.db.delete_cf(visited_cf, key).ok();
+        }
+        
+        self.add_to_frontier_with_priority(url, priority)
+    }
+    
+    /// Pop a URL from the frontier with its priority and mark it as visited
+    /// High-priority URLs are returned before the rest of the frontier
+    /// URLs of parked hosts are moved aside instead of being returned
     /// Returns None if frontier is empty
-    pub fn pop_from_frontier(&self) -> Option<String> {
+    pub fn pop_from_frontier(&self) -> Option<(String, u8)> {
         let frontier_cf = self.db.cf_handle("frontier").unwrap();
-        let visited_cf = self.db.cf_handle("visited").unwrap();
+        let priority_cf = self.db.cf_handle("frontier_priority").unwrap();
         
-        let mut iter = self.db.iterator_cf(frontier_cf, rocksdb::IteratorMode::Start);
-        if let Some(Ok((key, _))) = iter.next() {
-            let url = String::from_utf8_lossy(&key).to_string();
+        // Priority keys are prefixed with the inverted priority, so the highest sorts first
+        let iter = self.db.iterator_cf(priority_cf, rocksdb::IteratorMode::Start);
+        for (key, _) in iter.flatten() {
+            self.db.delete_cf(priority_cf, &key).ok();
+            let url_key = &key[1..];
             
-            // Move from frontier to visited
-            let timestamp = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-                .to_le_bytes();
-            self.db.put_cf(visited_cf, &key, timestamp).ok();
-            self.db.delete_cf(frontier_cf, &key).ok();
+            // Skip index entries whose URL already left the frontier
+            if let Ok(Some(value)) = self.db.get_cf(frontier_cf, url_key) {
+                let priority = Self::frontier_priority(&value);
+                if self.park_if_host_parked(url_key, priority) {
+                    continue;
+                }
+                self.move_to_visited(url_key);
+                return Some((String::from_utf8_lossy(url_key).to_string(), priority));
+            }
+        }
+        
+        let iter = self.db.iterator_cf(frontier_cf, rocksdb::IteratorMode::Start);
+        for (key, value) in iter.flatten() {
+            let priority = Self::frontier_priority(&value);
+            if priority > DEFAULT_PRIORITY {
+                self.db.delete_cf(priority_cf, Self::priority_key(&key, priority)).ok();
+            }
+            if self.park_if_host_parked(&key, priority) {
+                continue;
+            }
+            self.move_to_visited(&key);
             
-            Some(url)
-        } else {
-            None
+            return Some((String::from_utf8_lossy(&key).to_string(), priority));
+        }
+        None
+    }
+    
+    /// Hold back a URL and the rest of its host's frontier for `cool_down_secs`
+    /// The URL is taken out of visited so it is crawled again once released, at the priority it had
+    pub fn park(&self, url: &str, priority: u8, cool_down_secs: u64) {
+        let Some(host) = Self::host_of(url) else {
+            return;
+        };
+        let parked_hosts_cf = self.db.cf_handle("parked_hosts").unwrap();
+        let release_at = Self::now_secs() + cool_down_secs;
+        self.db.put_cf(parked_hosts_cf, host.as_bytes(), release_at.to_le_bytes()).ok();
+        
+        let normalized = normalize_url(url);
+        let visited_cf = self.db.cf_handle("visited").unwrap();
+        let parked_cf = self.db.cf_handle("parked").unwrap();
+        self.db.delete_cf(visited_cf, normalized.as_bytes()).ok();
+        self.db.put_cf(parked_cf, normalized.as_bytes(), [priority]).ok();
+    }
+    
+    /// Return the URLs of hosts whose cool-down has ended to the frontier
+    /// Returns the number of URLs re-queued
+    pub fn release_parked_hosts(&self) -> usize {
+        let parked_hosts_cf = self.db.cf_handle("parked_hosts").unwrap();
+        let parked_cf = self.db.cf_handle("parked").unwrap();
+        let now = Self::now_secs();
+        
+        let mut released = HashSet::new();
+        for (key, value) in self.db.iterator_cf(parked_hosts_cf, rocksdb::IteratorMode::Start).flatten() {
+            if Self::release_time(&value) <= now {
+                self.db.delete_cf(parked_hosts_cf, &key).ok();
+                released.insert(String::from_utf8_lossy(&key).to_string());
+            }
+        }
+        if released.is_empty() {
+            return 0;
+        }
+        
+        let mut requeued = 0;
+        for (key, value) in self.db.iterator_cf(parked_cf, rocksdb::IteratorMode::Start).flatten() {
+            let url = String::from_utf8_lossy(&key).to_string();
+            if Self::host_of(&url).is_some_and(|host| released.contains(&host)) {
+                self.db.delete_cf(parked_cf, &key).ok();
+                let priority = value.first().copied().unwrap_or(DEFAULT_PRIORITY);
+                if self.add_to_frontier_with_priority(&url, priority) {
+                    requeued += 1;
+                }
+            }
         }
+        requeued
     }
     
     /// Get count of URLs in frontier
@@ -131,53 +251,230 @@ impl UrlStore {
     
     /// Mark a URL as visited (for canonical URLs)
     pub fn mark_visited(&self, url: &str) {
-        let normalized = Self::normalize_url(url);
+        let normalized = normalize_url(url);
         let key = normalized.as_bytes();
         let visited_cf = self.db.cf_handle("visited").unwrap();
+        self.db.put_cf(visited_cf, key, Self::now_secs().to_le_bytes()).ok();
+    }
+    
+    /// Check whether a sitemap has not been fetched within `refresh_secs`
+    pub fn sitemap_due(&self, sitemap_url: &str, refresh_secs: u64) -> bool {
+        let sitemaps_cf = self.db.cf_handle("sitemaps").unwrap();
+        if let Ok(Some(bytes)) = self.db.get_cf(sitemaps_cf, sitemap_url.as_bytes())
+            && bytes.len() == 8 {
+            let fetched_at = u64::from_le_bytes(bytes[..8].try_into().unwrap_or([0u8; 8]));
+            return Self::now_secs().saturating_sub(fetched_at) >= refresh_secs;
+        }
+        true
+    }
+    
+    /// Record that a sitemap was just fetched
+    pub fn mark_sitemap_fetched(&self, sitemap_url: &str) {
+        let sitemaps_cf = self.db.cf_handle("sitemaps").unwrap();
+        self.db.put_cf(sitemaps_cf, sitemap_url.as_bytes(), Self::now_secs().to_le_bytes()).ok();
+    }
+    
+    /// Register a feed for polling, returns false if it is already known
+    pub fn add_feed(&self, feed_url: &str, interval_secs: u64) -> bool {
+        let feeds_cf = self.db.cf_handle("feeds").unwrap();
+        if self.db.get_cf(feeds_cf, feed_url.as_bytes()).unwrap_or(None).is_some() {
+            return false;
+        }
         
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs()
-            .to_le_bytes();
-        self.db.put_cf(visited_cf, key, timestamp).ok();
+        // New feeds are due immediately
+        self.put_feed_schedule(feed_url, Self::now_secs(), interval_secs);
+        true
     }
     
-    /// Normalize URL to reduce duplicates
-    fn normalize_url(url: &str) -> String {
-        let Ok(mut parsed) = Url::parse(url) else {
-            return url.to_string();
-        };
+    /// Feeds whose next poll time has passed, with their current interval
+    pub fn due_feeds(&self, limit: usize) -> Vec<(String, u64)> {
+        let feeds_cf = self.db.cf_handle("feeds").unwrap();
+        let now = Self::now_secs();
         
-        parsed.set_fragment(None);
+        self.db
+            .iterator_cf(feeds_cf, rocksdb::IteratorMode::Start)
+            .flatten()
+            .filter_map(|(key, value)| {
+                let (next_poll, interval) = Self::feed_schedule(&value)?;
+                (next_poll <= now).then(|| (String::from_utf8_lossy(&key).to_string(), interval))
+            })
+            .take(limit)
+            .collect()
+    }
+    
+    /// Schedule the next poll of a feed `interval_secs` from now
+    pub fn schedule_feed(&self, feed_url: &str, interval_secs: u64) {
+        self.put_feed_schedule(feed_url, Self::now_secs() + interval_secs, interval_secs);
+    }
+    
+    /// Feed value layout: [next_poll: u64 le][interval: u64 le]
+    fn put_feed_schedule(&self, feed_url: &str, next_poll: u64, interval_secs: u64) {
+        let feeds_cf = self.db.cf_handle("feeds").unwrap();
+        let mut value = next_poll.to_le_bytes().to_vec();
+        value.extend_from_slice(&interval_secs.to_le_bytes());
+        self.db.put_cf(feeds_cf, feed_url.as_bytes(), value).ok();
+    }
+    
+    fn feed_schedule(value: &[u8]) -> Option<(u64, u64)> {
+        let next_poll = u64::from_le_bytes(value.get(..8)?.try_into().ok()?);
+        let interval = u64::from_le_bytes(value.get(8..16)?.try_into().ok()?);
+        Some((next_poll, interval))
+    }
+    
+    /// Record the content hash of a page about to be written
+    /// Returns the URL of the page first seen with the same content when that is another page,
+    /// in which case this URL is recorded as its alias
+    pub fn record_content_hash(&self, content_hash: &str, url: &str) -> Option<String> {
+        let normalized = normalize_url(url);
+        let hashes_cf = self.db.cf_handle("content_hashes").unwrap();
         
-        let tracking_params = [
-            "utm_source", "utm_medium", "utm_campaign", "utm_term", "utm_content",
-            "fbclid", "gclid", "msclkid", "mc_cid", "mc_eid",
-        ];
+        let _guard = self.content_hashes_lock.lock().unwrap();
+        let Ok(Some(first)) = self.db.get_cf(hashes_cf, content_hash.as_bytes()) else {
+            self.db.put_cf(hashes_cf, content_hash.as_bytes(), normalized.as_bytes()).ok();
+            return None;
+        };
+        // A page crawled again with unchanged content is not its own duplicate
+        if first == normalized.as_bytes() {
+            return None;
+        }
         
-        let mut query_pairs: Vec<(String, String)> = parsed.query_pairs().into_owned().collect();
-        query_pairs.retain(|(key, _)| !tracking_params.contains(&key.as_str()));
+        let aliases_cf = self.db.cf_handle("aliases").unwrap();
+        self.db.put_cf(aliases_cf, normalized.as_bytes(), &first).ok();
+        Some(String::from_utf8_lossy(&first).to_string())
+    }
+    
+    /// Near-duplicate cluster of a page about to be written, by its SimHash fingerprint
+    /// The page joins the cluster of the closest fingerprint within `MAX_SIMHASH_DISTANCE` bits,
+    /// or starts one named by its own URL; its fingerprint is then recorded for later pages
+    pub fn simhash_cluster(&self, fingerprint: u64, url: &str) -> String {
+        let simhashes_cf = self.db.cf_handle("simhashes").unwrap();
+        let bands = dedup::simhash_bands(fingerprint);
         
-        if query_pairs.is_empty() {
-            parsed.set_query(None);
-        } else {
-            query_pairs.sort_by(|a, b| a.0.cmp(&b.0));
-            let query_string = query_pairs
-                .iter()
-                .map(|(k, v)| format!("{}={}", k, v))
-                .collect::<Vec<_>>()
-                .join("&");
-            parsed.set_query(Some(&query_string));
+        let _guard = self.simhashes_lock.lock().unwrap();
+        let mut closest: Option<(u32, String)> = None;
+        for (band, bits) in bands.iter().enumerate() {
+            let prefix = Self::simhash_key_prefix(band, *bits);
+            let iter = self.db.iterator_cf(simhashes_cf, rocksdb::IteratorMode::From(&prefix, rocksdb::Direction::Forward));
+            for (key, value) in iter.flatten().take_while(|(key, _)| key.starts_with(&prefix)).take(MAX_SIMHASH_BAND_SCAN) {
+                let Some(other) = key.get(3..11).and_then(|bytes| bytes.try_into().ok()).map(u64::from_be_bytes) else {
+                    continue;
+                };
+                let distance = dedup::hamming_distance(fingerprint, other);
+                if distance <= MAX_SIMHASH_DISTANCE && closest.as_ref().is_none_or(|(best, _)| distance < *best) {
+                    closest = Some((distance, String::from_utf8_lossy(&value).to_string()));
+                }
+            }
         }
         
-        let mut normalized = parsed.to_string();
-        
-        if normalized.ends_with('/') && normalized.matches('/').count() > 3 {
-            normalized.pop();
+        let cluster = closest.map(|(_, cluster)| cluster).unwrap_or_else(|| normalize_url(url));
+        for (band, bits) in bands.iter().enumerate() {
+            let mut key = Self::simhash_key_prefix(band, *bits);
+            key.extend_from_slice(&fingerprint.to_be_bytes());
+            self.db.put_cf(simhashes_cf, key, cluster.as_bytes()).ok();
+        }
+        cluster
+    }
+    
+    /// SimHash key layout: [band: u8][band bits: u16 be][fingerprint: u64 be]
+    fn simhash_key_prefix(band: usize, bits: u16) -> Vec<u8> {
+        let mut prefix = vec![band as u8];
+        prefix.extend_from_slice(&bits.to_be_bytes());
+        prefix
+    }
+    
+    /// Save a request profile's cookie jar
+    pub fn put_cookies(&self, host: &str, cookies: &str) {
+        let cookies_cf = self.db.cf_handle("cookies").unwrap();
+        self.db.put_cf(cookies_cf, host.as_bytes(), cookies.as_bytes()).ok();
+    }
+    
+    /// All saved cookie jars by profile host
+    pub fn cookies(&self) -> Vec<(String, String)> {
+        let cookies_cf = self.db.cf_handle("cookies").unwrap();
+        self.db
+            .iterator_cf(cookies_cf, rocksdb::IteratorMode::Start)
+            .flatten()
+            .map(|(key, value)| {
+                (String::from_utf8_lossy(&key).to_string(), String::from_utf8_lossy(&value).to_string())
+            })
+            .collect()
+    }
+    
+    /// Move a frontier entry into "parked" if its host is still cooling down
+    fn park_if_host_parked(&self, key: &[u8], priority: u8) -> bool {
+        let parked_hosts_cf = self.db.cf_handle("parked_hosts").unwrap();
+        let Some(host) = Self::host_of(&String::from_utf8_lossy(key)) else {
+            return false;
+        };
+        let Ok(Some(value)) = self.db.get_cf(parked_hosts_cf, host.as_bytes()) else {
+            return false;
+        };
+        if Self::release_time(&value) <= Self::now_secs() {
+            return false;
         }
         
-        normalized
+        let frontier_cf = self.db.cf_handle("frontier").unwrap();
+        let parked_cf = self.db.cf_handle("parked").unwrap();
+        self.db.put_cf(parked_cf, key, [priority]).ok();
+        self.db.delete_cf(frontier_cf, key).ok();
+        true
+    }
+    
+    fn release_time(value: &[u8]) -> u64 {
+        value.get(..8).and_then(|bytes| bytes.try_into().ok()).map(u64::from_le_bytes).unwrap_or(0)
+    }
+    
+    fn host_of(url: &str) -> Option<String> {
+        Url::parse(url).ok()?.host_str().map(|host| host.to_string())
+    }
+    
+    /// Move a frontier key into visited with the current timestamp
+    fn move_to_visited(&self, key: &[u8]) {
+        let frontier_cf = self.db.cf_handle("frontier").unwrap();
+        let visited_cf = self.db.cf_handle("visited").unwrap();
+        self.db.put_cf(visited_cf, key, Self::now_secs().to_le_bytes()).ok();
+        self.db.delete_cf(frontier_cf, key).ok();
+    }
+    
+    /// Write a frontier entry and keep the priority index in sync
+    fn set_frontier_entry(&self, key: &[u8], priority: u8, previous: Option<u8>) {
+        let frontier_cf = self.db.cf_handle("frontier").unwrap();
+        let priority_cf = self.db.cf_handle("frontier_priority").unwrap();
+        
+        // Frontier value layout: [timestamp: u64 le][priority: u8]
+        let mut value = Self::now_secs().to_le_bytes().to_vec();
+        value.push(priority);
+        self.db.put_cf(frontier_cf, key, value).unwrap_or_else(|e| {
+            eprintln!("Failed to add URL to frontier: {}", e);
+        });
+        
+        if let Some(previous) = previous
+            && previous > DEFAULT_PRIORITY {
+            self.db.delete_cf(priority_cf, Self::priority_key(key, previous)).ok();
+        }
+        if priority > DEFAULT_PRIORITY {
+            self.db.put_cf(priority_cf, Self::priority_key(key, priority), []).ok();
+        }
+    }
+    
+    /// Priority index key: inverted priority byte followed by the URL
+    fn priority_key(key: &[u8], priority: u8) -> Vec<u8> {
+        let mut priority_key = Vec::with_capacity(key.len() + 1);
+        priority_key.push(u8::MAX - priority);
+        priority_key.extend_from_slice(key);
+        priority_key
+    }
+    
+    /// Read the priority from a frontier value, entries from older runs have none
+    fn frontier_priority(value: &[u8]) -> u8 {
+        value.get(8).copied().unwrap_or(DEFAULT_PRIORITY)
+    }
+    
+    fn now_secs() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
     }
 }
 
@@ -186,6 +483,8 @@ impl Clone for UrlStore {
     fn clone(&self) -> Self {
         Self {
             db: self.db.clone(),
+            content_hashes_lock: self.content_hashes_lock.clone(),
+            simhashes_lock: self.simhashes_lock.clone(),
         }
     }
 }
@@ -1,6 +1,8 @@
+use crate::config::CrawlerConfig;
+use crate::images::Image;
 use crate::parser::ParsedHtml;
-use std::io::{BufWriter, Write};
-use std::fs::File;
+use crate::segments::SegmentedFile;
+use serde::Serialize;
 use tokio::sync::mpsc;
 use std::time::{Duration, Instant};
 
@@ -8,32 +10,82 @@ const BATCH_SIZE: usize = 100;
 const FLUSH_INTERVAL_SECS: u64 = 5;
 const MAX_BUFFER_SIZE: usize = 1_000_000;
 
+/// Line of the image stream: an image and the page it appears on
+#[derive(Serialize)]
+struct ImageRecord<'a> {
+    page_url: &'a str,
+    page_title: Option<&'a str>,
+    #[serde(flatten)]
+    image: &'a Image,
+}
+
+/// A segmented JSONL file with its pending batch of lines
+struct JsonlStream {
+    segments: SegmentedFile,
+    batch: Vec<String>,
+    batch_bytes: usize,
+}
+
+impl JsonlStream {
+    fn open(file_path: &str, config: &CrawlerConfig) -> Result<Self, std::io::Error> {
+        Ok(Self {
+            segments: SegmentedFile::open(file_path, config)?,
+            batch: Vec::with_capacity(BATCH_SIZE),
+            batch_bytes: 0,
+        })
+    }
+    
+    fn push(&mut self, json_line: String) {
+        self.batch_bytes += json_line.len() + 1;
+        self.batch.push(json_line);
+    }
+    
+    /// Write the batch to the current segment, and finish the segment if it is full or old enough
+    /// Lines leave the batch as they are written, so a failed flush is retried from the first unwritten line
+    fn flush(&mut self) -> std::io::Result<()> {
+        let mut written = 0;
+        let result = self
+            .batch
+            .iter()
+            .try_for_each(|line| self.segments.write_line(line).map(|()| written += 1));
+        
+        let written_bytes: usize = self.batch.drain(..written).map(|line| line.len() + 1).sum();
+        self.batch_bytes -= written_bytes;
+        result?;
+        
+        self.segments.rotate_if_due()
+    }
+    
+    fn close(&mut self) -> std::io::Result<()> {
+        self.flush()?;
+        self.segments.close()
+    }
+}
+
 /// Buffered writer that batches JSONL writes for better performance
+/// Pages go to one segmented stream and their images, one record per image, to another
+/// Open segments are finished once the channel closes
 pub struct BufferedWriter {
     receiver: mpsc::Receiver<ParsedHtml>,
-    writer: BufWriter<File>,
-    batch: Vec<String>,
-    batch_bytes: usize,
+    pages: JsonlStream,
+    images: JsonlStream,
     last_flush: Instant,
 }
 
 impl BufferedWriter {
     /// Create a new buffered writer and return the sender channel
-    pub fn new(file_path: &str) -> Result<(Self, mpsc::Sender<ParsedHtml>), std::io::Error> {
-        let file = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(file_path)?;
-        
-        let writer = BufWriter::with_capacity(8192, file);
+    pub fn new(
+        file_path: &str,
+        images_path: &str,
+        config: &CrawlerConfig,
+    ) -> Result<(Self, mpsc::Sender<ParsedHtml>), std::io::Error> {
         let (sender, receiver) = mpsc::channel(1000);
         
         Ok((
             Self {
                 receiver,
-                writer,
-                batch: Vec::with_capacity(BATCH_SIZE),
-                batch_bytes: 0,
+                pages: JsonlStream::open(file_path, config)?,
+                images: JsonlStream::open(images_path, config)?,
                 last_flush: Instant::now(),
             },
             sender,
@@ -42,10 +94,20 @@ impl BufferedWriter {
     
     /// Run the writer loop - call this in a tokio task
     pub async fn run(mut self) {
-        while let Some(parsed) = self.receiver.recv().await {
-            if let Err(e) = self.add_to_batch(parsed) {
-                eprintln!("Failed to serialize data: {}", e);
-                continue;
+        // Wakes the writer while pages are slow to come, so batches and old segments don't linger
+        let mut ticker = tokio::time::interval(Duration::from_secs(FLUSH_INTERVAL_SECS));
+        loop {
+            tokio::select! {
+                received = self.receiver.recv() => {
+                    let Some(parsed) = received else {
+                        break;
+                    };
+                    if let Err(e) = self.add_to_batch(parsed) {
+                        eprintln!("Failed to serialize data: {}", e);
+                        continue;
+                    }
+                }
+                _ = ticker.tick() => {}
             }
             
             if self.should_flush()
@@ -54,35 +116,41 @@ impl BufferedWriter {
             }
         }
         
-        let _ = self.flush();
-        let _ = self.writer.flush();
+        if let Err(e) = self.pages.close().and(self.images.close()) {
+            eprintln!("Failed to finish output segments: {}", e);
+        }
     }
     
     fn add_to_batch(&mut self, parsed: ParsedHtml) -> Result<(), serde_json::Error> {
         let json_line = serde_json::to_string(&parsed)?;
-        self.batch_bytes += json_line.len() + 1;
-        self.batch.push(json_line);
+        let image_lines = parsed
+            .images
+            .iter()
+            .map(|image| {
+                serde_json::to_string(&ImageRecord {
+                    page_url: &parsed.url,
+                    page_title: parsed.title.as_deref(),
+                    image,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        
+        self.pages.push(json_line);
+        for line in image_lines {
+            self.images.push(line);
+        }
         Ok(())
     }
     
     fn should_flush(&self) -> bool {
-        self.batch.len() >= BATCH_SIZE
-            || self.batch_bytes >= MAX_BUFFER_SIZE
+        self.pages.batch.len() >= BATCH_SIZE
+            || self.pages.batch_bytes + self.images.batch_bytes >= MAX_BUFFER_SIZE
             || self.last_flush.elapsed() >= Duration::from_secs(FLUSH_INTERVAL_SECS)
     }
     
     fn flush(&mut self) -> std::io::Result<()> {
-        if self.batch.is_empty() {
-            return Ok(());
-        }
-        
-        for line in &self.batch {
-            writeln!(self.writer, "{}", line)?;
-        }
-        
-        self.writer.flush()?;
-        self.batch.clear();
-        self.batch_bytes = 0;
+        self.pages.flush()?;
+        self.images.flush()?;
         self.last_flush = Instant::now();
         
         Ok(())